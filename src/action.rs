@@ -0,0 +1,157 @@
+//! What can be done with the target directories that are cleaned
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::{quarantine, unix_secs, Candidate, Error};
+
+/// Something done with the target directory of a candidate
+pub trait Action {
+    /// Does this with the target directory of `candidate`, which is left in place if it fails
+    fn apply(&mut self, candidate: &Candidate) -> Result<(), Error>;
+}
+
+/// Deletes target directories permanently
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Delete;
+impl Action for Delete {
+    fn apply(&mut self, candidate: &Candidate) -> Result<(), Error> {
+        std::fs::remove_dir_all(&candidate.target_path).map_err(|source| Error::Delete {
+            path: candidate.target_path.clone(),
+            source,
+        })
+    }
+}
+
+/// Moves target directories to the freedesktop.org trash
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Trash;
+impl Action for Trash {
+    fn apply(&mut self, candidate: &Candidate) -> Result<(), Error> {
+        move_to_trash(candidate)
+            .map(|_| ())
+            .map_err(|source| Error::Trash {
+                path: candidate.target_path.clone(),
+                source,
+            })
+    }
+}
+#[cfg(unix)]
+fn move_to_trash(candidate: &Candidate) -> io::Result<std::path::PathBuf> {
+    crate::trash::move_to_trash(&candidate.target_path)
+}
+#[cfg(not(unix))]
+fn move_to_trash(_candidate: &Candidate) -> io::Result<std::path::PathBuf> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the trash is only supported on unix",
+    ))
+}
+
+/// Moves target directories into quarantine, from where they can be restored until they are purged
+#[derive(Debug, Clone, Copy)]
+pub struct Quarantine {
+    /// When the run started, which groups everything quarantined in it together
+    pub started: SystemTime,
+    /// Whether the apparent size is recorded instead of the space used on disk
+    pub apparent_size: bool,
+}
+impl Action for Quarantine {
+    fn apply(&mut self, candidate: &Candidate) -> Result<(), Error> {
+        let size = if self.apparent_size {
+            candidate.apparent_size
+        } else {
+            candidate.size
+        };
+        quarantine::quarantine(&candidate.target_path, size, self.started)
+            .map(|_| ())
+            .map_err(|source| Error::Quarantine {
+                path: candidate.target_path.clone(),
+                source,
+            })
+    }
+}
+
+/// Packs target directories into gzipped tarballs in a directory, then deletes them
+///
+/// Archives are named after the project and hold the target directory under its own name, so
+/// that unpacking one next to the project's manifest puts it back
+#[derive(Debug, Clone)]
+pub struct Archive {
+    /// Where the archives are written, which is created if needed
+    pub dir: PathBuf,
+}
+impl Action for Archive {
+    fn apply(&mut self, candidate: &Candidate) -> Result<(), Error> {
+        let path = &candidate.target_path;
+        archive(&self.dir, path).map_err(|source| Error::Archive {
+            path: path.clone(),
+            source,
+        })?;
+        fs::remove_dir_all(path).map_err(|source| Error::Delete {
+            path: path.clone(),
+            source,
+        })
+    }
+}
+/// Writes the directory at `path` to a new archive in `dir`, returning where it is
+fn archive(dir: &Path, path: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let now = unix_secs(SystemTime::now());
+    let dir_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "the path has no name"))?;
+    let project_name = path
+        .parent()
+        .and_then(|parent| parent.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut i = 1;
+    let (archive_path, file) = loop {
+        let suffix = if i == 1 {
+            String::new()
+        } else {
+            format!(".{i}")
+        };
+        let archive_path = dir.join(format!("{now}-{project_name}{suffix}.tar.gz"));
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&archive_path)
+        {
+            Ok(file) => break (archive_path, file),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => i += 1,
+            Err(e) => return Err(e),
+        }
+    };
+    let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+    // Symlinks are archived as they are, rather than what they point to
+    builder.follow_symlinks(false);
+    let written = builder
+        .append_dir_all(dir_name, path)
+        .and_then(|()| builder.into_inner()?.finish()?.sync_all());
+    if let Err(e) = written {
+        // A partial archive is worse than none, as it looks like the target directory is safe
+        let _ = fs::remove_file(&archive_path);
+        return Err(e);
+    }
+    Ok(archive_path)
+}
+
+/// Writes each target directory to a writer as a line of JSON, leaving it in place
+#[derive(Debug)]
+pub struct Report<W>(pub W);
+impl<W: Write> Action for Report<W> {
+    fn apply(&mut self, candidate: &Candidate) -> Result<(), Error> {
+        serde_json::to_writer(&mut self.0, candidate)
+            .map_err(io::Error::from)
+            .and_then(|()| writeln!(self.0))
+            .map_err(|source| Error::Report {
+                path: candidate.target_path.clone(),
+                source,
+            })
+    }
+}
@@ -0,0 +1,174 @@
+use clap::ValueEnum;
+use git2::{ErrorCode, Repository};
+use std::ffi::OsString;
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+use crate::report;
+
+/// Which files are looked at to decide whether a project is still in use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ActivitySource {
+    /// Only files inside the target directory, which change when the project is built
+    Target,
+    /// Only the project's own files outside of the target directory
+    Sources,
+    /// Both the target directory and the project's sources
+    Both,
+}
+impl ActivitySource {
+    pub fn includes_target(self) -> bool {
+        matches!(self, ActivitySource::Target | ActivitySource::Both)
+    }
+    pub fn includes_sources(self) -> bool {
+        matches!(self, ActivitySource::Sources | ActivitySource::Both)
+    }
+}
+
+/// Returns the modification time of the newest file in `paths`, leaving out the artifact
+/// directories in `artifacts`
+///
+/// The walk stops at the first file modified after `stop_after` if given, which is then returned
+/// instead. Directories whose name is in `ignored_dirs` are skipped entirely
+pub fn newest_source_modification(
+    paths: &[PathBuf],
+    artifacts: &[PathBuf],
+    stop_after: Option<SystemTime>,
+    ignored_dirs: &[OsString],
+) -> Option<SystemTime> {
+    let mut newest = None;
+    for path in paths {
+        let walker = WalkDir::new(path).into_iter().filter_entry(|entry| {
+            let artifact = artifacts.iter().any(|artifact| entry.path() == artifact);
+            let ignored = entry.file_type().is_dir()
+                && entry.depth() > 0
+                && ignored_dirs.iter().any(|name| name == entry.file_name());
+            !artifact && !ignored
+        });
+        for entry in walker {
+            match entry {
+                Ok(entry) => {
+                    // A project directory itself changes whenever the target directory is created
+                    // or removed
+                    if entry.depth() == 0 && entry.file_type().is_dir() {
+                        continue;
+                    }
+                    let modified = entry
+                        .metadata()
+                        .map_err(io::Error::from)
+                        .and_then(|metadata| metadata.modified());
+                    match modified {
+                        Ok(time) => {
+                            if stop_after.is_some_and(|stop_after| time > stop_after) {
+                                return Some(time);
+                            }
+                            newest = newest.max(Some(time));
+                        }
+                        Err(e) => report::error(
+                            entry.path(),
+                            e.kind(),
+                            format!(
+                                "Error accessing metadata of file {}: {}",
+                                entry.path().display(),
+                                e
+                            ),
+                        ),
+                    }
+                }
+                Err(e) => report::error(
+                    e.path().unwrap_or(path),
+                    e.io_error().map_or(ErrorKind::Other, |e| e.kind()),
+                    format!("Error accessing entry in folder: {e}"),
+                ),
+            }
+        }
+    }
+    newest
+}
+
+/// A piece of git history that shows when a repository was last used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitSignal {
+    /// The commit time of the commit HEAD points to
+    HeadCommit,
+    /// The newest entry in HEAD's reflog, which covers checkouts, pulls and resets
+    Reflog,
+    /// The modification time of the index, which changes on staging and most checkouts
+    Index,
+}
+impl fmt::Display for GitSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            GitSignal::HeadCommit => "last commit on HEAD",
+            GitSignal::Reflog => "last reflog entry",
+            GitSignal::Index => "git index modification",
+        })
+    }
+}
+
+/// Returns the most recent activity recorded in the git repository containing `project_dir`
+///
+/// Only the local `.git` directory is read. Returns `None` if the project isn't in a repository
+pub fn git_activity(project_dir: &Path) -> Option<(SystemTime, GitSignal)> {
+    let repo = Repository::discover(project_dir).ok()?;
+    let mut signals = Vec::new();
+    match repo.head().and_then(|head| head.peel_to_commit()) {
+        Ok(commit) => signals.push((git_time(commit.time()), GitSignal::HeadCommit)),
+        // A freshly initialized repository has no commits yet
+        Err(e) if e.code() == ErrorCode::UnbornBranch => (),
+        Err(e) => report::error(
+            repo.path(),
+            ErrorKind::Other,
+            format!(
+                "Error reading HEAD commit of repository {}: {}",
+                repo.path().display(),
+                e
+            ),
+        ),
+    }
+    match repo.reflog("HEAD") {
+        Ok(reflog) => {
+            if let Some(entry) = reflog.get(0) {
+                signals.push((git_time(entry.committer().when()), GitSignal::Reflog));
+            }
+        }
+        Err(e) => report::error(
+            repo.path(),
+            ErrorKind::Other,
+            format!(
+                "Error reading reflog of repository {}: {}",
+                repo.path().display(),
+                e
+            ),
+        ),
+    }
+    if let Ok(time) = repo
+        .path()
+        .join("index")
+        .metadata()
+        .and_then(|m| m.modified())
+    {
+        signals.push((time, GitSignal::Index));
+    }
+    signals.into_iter().max_by_key(|(time, _)| *time)
+}
+
+fn git_time(time: git2::Time) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(time.seconds().max(0) as u64)
+}
+
+/// Formats how long ago `time` was, such as "12 days ago"
+pub fn describe_age(time: SystemTime) -> String {
+    let days = SystemTime::now()
+        .duration_since(time)
+        .map(|age| age.as_secs() / (3600 * 24))
+        .unwrap_or(0);
+    match days {
+        0 => "today".to_owned(),
+        1 => "1 day ago".to_owned(),
+        days => format!("{days} days ago"),
+    }
+}
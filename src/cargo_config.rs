@@ -0,0 +1,197 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs::read_to_string;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::report;
+
+/// Returns the target directory cargo would use when run from `project_dir`
+///
+/// This follows the same order cargo does: `CARGO_TARGET_DIR`, then `CARGO_BUILD_TARGET_DIR`,
+/// then `build.target-dir` from the closest `.cargo/config.toml` in `project_dir` or any parent,
+/// then `$CARGO_HOME/config.toml`, and finally `target` next to the manifest
+pub fn resolve_target_dir(project_dir: &Path) -> PathBuf {
+    target_dir_with(project_dir, |var| env::var_os(var))
+}
+
+/// Resolves the target directory as [`resolve_target_dir`] does, reading environment variables
+/// through `var`
+fn target_dir_with(project_dir: &Path, var: impl Fn(&str) -> Option<OsString>) -> PathBuf {
+    for name in ["CARGO_TARGET_DIR", "CARGO_BUILD_TARGET_DIR"] {
+        if let Some(value) = var(name) {
+            if !value.is_empty() {
+                // Relative paths in the environment are relative to where cargo is run from
+                return project_dir.join(value);
+            }
+        }
+    }
+    for ancestor in project_dir.ancestors() {
+        if let Some(target_dir) = config_target_dir(&ancestor.join(".cargo")) {
+            return target_dir;
+        }
+    }
+    if let Some(cargo_home) = cargo_home(&var) {
+        if let Some(target_dir) = config_target_dir(&cargo_home) {
+            return target_dir;
+        }
+    }
+    project_dir.join("target")
+}
+
+/// Returns the cargo home directory, which holds the user wide config file
+fn cargo_home(var: impl Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    match var("CARGO_HOME") {
+        Some(value) if !value.is_empty() => Some(PathBuf::from(value)),
+        _ => var("HOME").map(|home| PathBuf::from(home).join(".cargo")),
+    }
+}
+
+/// Reads `build.target-dir` from the config file in the given `.cargo` directory, if any
+fn config_target_dir(cargo_dir: &Path) -> Option<PathBuf> {
+    // Cargo prefers the legacy extensionless file when both exist
+    let config_path = ["config", "config.toml"]
+        .iter()
+        .map(|name| cargo_dir.join(name))
+        .find(|path| path.is_file())?;
+    let contents = match read_to_string(&config_path) {
+        Ok(v) => v,
+        Err(e) => {
+            report::error(
+                &config_path,
+                e.kind(),
+                format!(
+                    "Error reading cargo config {}: {}",
+                    config_path.display(),
+                    e
+                ),
+            );
+            return None;
+        }
+    };
+    let config = match contents.parse::<toml::Table>() {
+        Ok(v) => v,
+        Err(e) => {
+            report::error(
+                &config_path,
+                ErrorKind::InvalidData,
+                format!(
+                    "Error parsing cargo config {}: {}",
+                    config_path.display(),
+                    e
+                ),
+            );
+            return None;
+        }
+    };
+    let target_dir = config.get("build")?.get("target-dir")?.as_str()?;
+    // Relative paths in config files are relative to the directory containing `.cargo`
+    let base = cargo_dir.parent().unwrap_or(cargo_dir);
+    Some(base.join(target_dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::tree;
+
+    /// Resolves the target directory with only the given environment variables set
+    fn resolve(project_dir: &Path, vars: &[(&str, &Path)]) -> PathBuf {
+        target_dir_with(project_dir, |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.as_os_str().to_owned())
+        })
+    }
+
+    #[test]
+    fn defaults_to_target_next_to_the_manifest() {
+        let dir = tree(&[("p/Cargo.toml", "")]);
+        let project = dir.path().join("p");
+        let home = dir.path().join("home");
+        assert_eq!(
+            resolve(&project, &[("HOME", &home)]),
+            project.join("target")
+        );
+    }
+
+    #[test]
+    fn prefers_the_environment_over_config_files() {
+        let dir = tree(&[
+            ("p/Cargo.toml", ""),
+            ("p/.cargo/config.toml", "[build]\ntarget-dir = \"config\"\n"),
+        ]);
+        let project = dir.path().join("p");
+        let shared = dir.path().join("shared");
+        assert_eq!(resolve(&project, &[("CARGO_TARGET_DIR", &shared)]), shared);
+        assert_eq!(
+            resolve(&project, &[("CARGO_BUILD_TARGET_DIR", &shared)]),
+            shared
+        );
+        // Relative paths are relative to where cargo runs, which is taken to be the project
+        assert_eq!(
+            resolve(&project, &[("CARGO_TARGET_DIR", Path::new("out"))]),
+            project.join("out")
+        );
+        // Empty variables count as unset
+        assert_eq!(
+            resolve(&project, &[("CARGO_TARGET_DIR", Path::new(""))]),
+            project.join("config")
+        );
+    }
+
+    #[test]
+    fn inherits_the_target_dir_of_parent_config_files() {
+        let dir = tree(&[
+            (
+                ".cargo/config.toml",
+                "[build]\ntarget-dir = \"build/shared\"\n",
+            ),
+            ("a/b/Cargo.toml", ""),
+            ("c/Cargo.toml", ""),
+            ("c/.cargo/config", "[build]\ntarget-dir = \"/absolute\"\n"),
+            (
+                "c/.cargo/config.toml",
+                "[build]\ntarget-dir = \"ignored\"\n",
+            ),
+        ]);
+        let home = dir.path().join("home");
+        // Relative to the directory holding .cargo, not to the project
+        assert_eq!(
+            resolve(&dir.path().join("a/b"), &[("HOME", &home)]),
+            dir.path().join("build/shared")
+        );
+        // The closest config file wins, and the legacy name wins over config.toml
+        assert_eq!(
+            resolve(&dir.path().join("c"), &[("HOME", &home)]),
+            PathBuf::from("/absolute")
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_config_in_cargo_home() {
+        let dir = tree(&[
+            ("p/Cargo.toml", ""),
+            (
+                "home/.cargo/config.toml",
+                "[build]\ntarget-dir = \"home-target\"\n",
+            ),
+            (
+                "cargo-home/config.toml",
+                "[build]\ntarget-dir = \"cargo-home-target\"\n",
+            ),
+        ]);
+        let project = dir.path().join("p");
+        let home = dir.path().join("home");
+        let cargo_home = dir.path().join("cargo-home");
+        assert_eq!(
+            resolve(&project, &[("HOME", &home)]),
+            home.join("home-target")
+        );
+        // Relative to the parent of cargo home, as for any other .cargo directory
+        assert_eq!(
+            resolve(&project, &[("HOME", &home), ("CARGO_HOME", &cargo_home)]),
+            dir.path().join("cargo-home-target")
+        );
+    }
+}
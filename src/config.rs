@@ -0,0 +1,267 @@
+//! The configuration file, which lists several roots to clean with their own policies
+//!
+//! ```toml
+//! days_old = 30
+//!
+//! [[root]]
+//! path = "~/code"
+//! mode = "trash"
+//!
+//! [[root]]
+//! path = "~/scratch"
+//! days_old = 7
+//! min_size = "100MB"
+//! exclude = ["keep-*"]
+//! detectors = ["cargo"]
+//!
+//! [profile.ci]
+//! mode = "delete"
+//!
+//! [[profile.ci.root]]
+//! path = "/builds"
+//! days_old = 3
+//! ```
+//!
+//! Settings at the top of the file or of a profile apply to all of its roots that don't set their
+//! own, and anything set on neither falls back to the command line. The mode is the exception, as
+//! `--actually-delete`, `--trash` and `--quarantine` apply to every root when given
+use glob::Pattern;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::detector::{self, Detector};
+use crate::goal::parse_size;
+use crate::xdg::{self, APP_NAME};
+use crate::CleanMode;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Config {
+    #[serde(flatten)]
+    pub defaults: Policy,
+    #[serde(default, rename = "root")]
+    pub roots: Vec<Root>,
+    #[serde(default, rename = "profile")]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// A named set of roots, run instead of the roots at the top of the file
+#[derive(Debug, Default, Deserialize)]
+pub struct Profile {
+    #[serde(flatten)]
+    pub defaults: Policy,
+    #[serde(default, rename = "root")]
+    pub roots: Vec<Root>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Root {
+    pub path: PathBuf,
+    /// Shown in the summary instead of the path
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub policy: Policy,
+}
+
+/// How the target directories below a root are cleaned, anything left out is inherited
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Policy {
+    pub days_old: Option<usize>,
+    /// Target directories smaller than this many bytes are kept
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub min_size: Option<u64>,
+    /// Globs of directories that aren't scanned
+    pub exclude: Option<Vec<String>>,
+    pub mode: Option<CleanMode>,
+    /// Names of the only detectors to scan with
+    pub detectors: Option<Vec<String>>,
+    /// Names of detectors not to scan with
+    pub disable_detectors: Option<Vec<String>>,
+}
+impl Policy {
+    /// Fills in everything that isn't set with the values of `fallback`
+    pub fn or(self, fallback: &Policy) -> Policy {
+        Policy {
+            days_old: self.days_old.or(fallback.days_old),
+            min_size: self.min_size.or(fallback.min_size),
+            exclude: self.exclude.or_else(|| fallback.exclude.clone()),
+            mode: self.mode.or(fallback.mode),
+            detectors: self.detectors.or_else(|| fallback.detectors.clone()),
+            disable_detectors: self
+                .disable_detectors
+                .or_else(|| fallback.disable_detectors.clone()),
+        }
+    }
+    /// Parses the exclude globs
+    pub fn exclude_patterns(&self) -> Result<Vec<Pattern>, String> {
+        self.exclude
+            .iter()
+            .flatten()
+            .map(|glob| {
+                Pattern::new(glob).map_err(|e| format!("invalid exclude glob {glob:?}: {e}"))
+            })
+            .collect()
+    }
+    /// Looks up the detectors to scan with
+    pub fn detectors(&self) -> Result<Vec<&'static dyn Detector>, String> {
+        let none = Vec::new();
+        detector::select(
+            self.detectors.as_ref().unwrap_or(&none),
+            self.disable_detectors.as_ref().unwrap_or(&none),
+        )
+    }
+}
+
+/// Returns where the configuration file is looked for when `--config` isn't given
+pub fn default_path() -> Option<PathBuf> {
+    Some(xdg::config_home()?.join(APP_NAME).join("config.toml"))
+}
+
+/// Reads the configuration file at `path`
+pub fn read(path: &Path) -> io::Result<Config> {
+    let contents = fs::read_to_string(path)?;
+    toml::from_str(&contents).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
+
+impl Config {
+    /// Returns the roots of `profile`, or the roots at the top of the file if it is `None`, with
+    /// their policies filled in from the file and profile
+    ///
+    /// Relative paths are relative to `base_dir`, and a leading `~` is the home directory
+    pub fn roots(&self, profile: Option<&str>, base_dir: &Path) -> Result<Vec<Root>, String> {
+        let (defaults, roots) = match profile {
+            Some(name) => {
+                let profile = self.profiles.get(name).ok_or_else(|| {
+                    let names: Vec<_> = self.profiles.keys().map(String::as_str).collect();
+                    format!(
+                        "there is no profile named {name:?}, the profiles are: {}",
+                        names.join(", ")
+                    )
+                })?;
+                (profile.defaults.clone().or(&self.defaults), &profile.roots)
+            }
+            None => (self.defaults.clone(), &self.roots),
+        };
+        if roots.is_empty() {
+            return Err("no roots are configured".to_owned());
+        }
+        Ok(roots
+            .iter()
+            .map(|root| Root {
+                path: base_dir.join(expand_home(&root.path)),
+                name: root.name.clone(),
+                policy: root.policy.clone().or(&defaults),
+            })
+            .collect())
+    }
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Reads a size given either as a number of bytes or as a string such as "100MB"
+fn deserialize_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Text(String),
+    }
+    match Size::deserialize(deserializer)? {
+        Size::Bytes(bytes) => Ok(Some(bytes)),
+        Size::Text(text) => parse_size(&text)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+days_old = 30
+exclude = ["vendor"]
+
+[[root]]
+path = "code"
+mode = "trash"
+
+[[root]]
+path = "/scratch"
+name = "scratch"
+days_old = 7
+min_size = "1KB"
+
+[profile.ci]
+mode = "delete"
+min_size = 100
+
+[[profile.ci.root]]
+path = "builds"
+days_old = 3
+
+[[profile.ci.root]]
+path = "cache"
+mode = "quarantine"
+exclude = []
+"#;
+
+    fn roots(profile: Option<&str>) -> Vec<Root> {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        config.roots(profile, Path::new("/base")).unwrap()
+    }
+
+    #[test]
+    fn fills_in_roots_from_the_top_of_the_file() {
+        let roots = roots(None);
+        assert_eq!(roots.len(), 2);
+        assert_eq!(roots[0].path, Path::new("/base/code"));
+        assert_eq!(roots[0].policy.days_old, Some(30));
+        assert_eq!(roots[0].policy.mode, Some(CleanMode::Trash));
+        assert_eq!(roots[0].policy.exclude, Some(vec!["vendor".to_owned()]));
+        assert_eq!(roots[0].policy.min_size, None);
+        assert_eq!(roots[1].path, Path::new("/scratch"));
+        assert_eq!(roots[1].name.as_deref(), Some("scratch"));
+        assert_eq!(roots[1].policy.days_old, Some(7));
+        assert_eq!(roots[1].policy.min_size, Some(1000));
+        assert_eq!(roots[1].policy.mode, None);
+    }
+
+    #[test]
+    fn fills_in_profile_roots_from_the_profile_then_the_file() {
+        let roots = roots(Some("ci"));
+        assert_eq!(roots.len(), 2);
+        assert_eq!(roots[0].path, Path::new("/base/builds"));
+        assert_eq!(roots[0].policy.days_old, Some(3));
+        assert_eq!(roots[0].policy.mode, Some(CleanMode::Delete));
+        assert_eq!(roots[0].policy.min_size, Some(100));
+        assert_eq!(roots[0].policy.exclude, Some(vec!["vendor".to_owned()]));
+        // Empty lists are set too, rather than left out
+        assert_eq!(roots[1].policy.days_old, Some(30));
+        assert_eq!(roots[1].policy.mode, Some(CleanMode::Quarantine));
+        assert_eq!(roots[1].policy.exclude, Some(Vec::new()));
+    }
+
+    #[test]
+    fn rejects_unknown_profiles_and_missing_roots() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        let error = config.roots(Some("nightly"), Path::new("")).unwrap_err();
+        assert_eq!(
+            error,
+            "there is no profile named \"nightly\", the profiles are: ci"
+        );
+        let empty: Config = toml::from_str("days_old = 3").unwrap();
+        assert_eq!(
+            empty.roots(None, Path::new("")).unwrap_err(),
+            "no roots are configured"
+        );
+    }
+}
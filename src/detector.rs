@@ -0,0 +1,507 @@
+//! Recognizing projects of the different build systems and the artifact directories they leave
+//! behind
+//!
+//! Each [`Detector`] looks at the entries of every scanned directory. The built in ones are in the
+//! [`BUILTIN`] registry, which `--detector` and `--no-detector` pick from
+use std::collections::BTreeSet;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::fs::read_to_string;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::manifest::Project;
+use crate::report;
+
+mod cargo;
+mod jvm;
+mod marker;
+mod native;
+mod node;
+mod python;
+pub use cargo::Cargo;
+pub use jvm::{Gradle, Maven, Sbt};
+pub use marker::{Elixir, Haskell, Ocaml, Zig};
+pub use native::{Cmake, Meson, Ninja};
+pub use node::Node;
+pub use python::Python;
+
+/// Recognizes the projects of one build system
+pub trait Detector: fmt::Debug + Sync {
+    /// Name used to enable or disable the detector, which is also the kind of its candidates
+    fn name(&self) -> &'static str;
+    /// Whether a project with artifacts in a directory means that nothing below it is scanned,
+    /// unless nested projects are asked for. Artifact directories themselves are never scanned
+    fn stops_descent(&self) -> bool {
+        false
+    }
+    /// Looks for projects in `dir`, whose entries are listed in `listing`, returning them along
+    /// with the artifact directories of theirs that exist
+    fn detect(&self, dir: &Path, listing: &Listing) -> Vec<Detection>;
+}
+
+/// The names of the entries of a directory
+#[derive(Debug, Clone, Default)]
+pub struct Listing {
+    /// Names of every entry
+    pub names: BTreeSet<OsString>,
+    /// Names of the subdirectories, including symlinks to directories
+    pub dirs: BTreeSet<OsString>,
+}
+impl Listing {
+    pub fn contains(&self, name: impl AsRef<OsStr>) -> bool {
+        self.names.contains(name.as_ref())
+    }
+    pub fn has_dir(&self, name: impl AsRef<OsStr>) -> bool {
+        self.dirs.contains(name.as_ref())
+    }
+    /// Lists the entries of `dir`, for looking at a directory again outside of a scan
+    pub fn read(dir: &Path) -> io::Result<Listing> {
+        let mut listing = Listing::default();
+        for entry in dir.read_dir()? {
+            let entry = entry?;
+            if entry.path().is_dir() {
+                listing.dirs.insert(entry.file_name());
+            }
+            listing.names.insert(entry.file_name());
+        }
+        Ok(listing)
+    }
+}
+
+/// A project a detector found
+#[derive(Debug, Clone)]
+pub struct Detection {
+    pub project: Project,
+    /// Artifact directories of the project that exist, each of which is cleaned on its own
+    pub artifacts: Vec<Artifact>,
+    /// Files and directories whose modification counts as activity in the project's sources,
+    /// artifact directories inside them are skipped
+    pub activity: Vec<PathBuf>,
+    /// Whether the project is somewhere below the directory that was looked at, such as a
+    /// workspace member. Only projects in the directory itself can stop the scan from descending
+    /// further
+    pub nested: bool,
+}
+
+/// A directory of build output that can be recreated
+#[derive(Debug, Clone)]
+pub struct Artifact {
+    pub path: PathBuf,
+    /// Whether this was left over by building the project in an unusual way, such as a workspace
+    /// member built on its own
+    pub stray: bool,
+}
+
+/// Every detector that comes with the cleaner, in the order they run in
+pub static BUILTIN: &[&dyn Detector] = &[
+    &Cargo, &Node, &Python, &Gradle, &Maven, &Sbt, &Cmake, &Meson, &Ninja, &Haskell, &Elixir,
+    &Ocaml, &Zig,
+];
+
+/// Returns the built in detector called `name`
+pub fn find(name: &str) -> Result<&'static dyn Detector, String> {
+    BUILTIN
+        .iter()
+        .copied()
+        .find(|detector| detector.name() == name)
+        .ok_or_else(|| {
+            let names: Vec<_> = BUILTIN.iter().map(|detector| detector.name()).collect();
+            format!(
+                "there is no detector named {name:?}, the detectors are: {}",
+                names.join(", ")
+            )
+        })
+}
+
+/// Returns the detectors named in `enable`, or all built in ones if it is empty, without those
+/// named in `disable`
+pub fn select(enable: &[String], disable: &[String]) -> Result<Vec<&'static dyn Detector>, String> {
+    let enabled = if enable.is_empty() {
+        BUILTIN.to_vec()
+    } else {
+        enable
+            .iter()
+            .map(|name| find(name))
+            .collect::<Result<_, _>>()?
+    };
+    for name in disable {
+        find(name)?;
+    }
+    Ok(enabled
+        .into_iter()
+        .filter(|detector| !disable.iter().any(|name| name == detector.name()))
+        .collect())
+}
+
+/// Returns the real path of a project directory, so that config files and workspaces in parents
+/// of the scanned root are found too
+pub fn project_dir(dir: &Path) -> PathBuf {
+    dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf())
+}
+
+/// Reads a top level list of strings from a YAML file, like the `packages` of a
+/// `pnpm-workspace.yaml` or `stack.yaml`, which are simple enough to not need a YAML parser
+pub fn yaml_list(path: &Path, key: &str) -> Vec<String> {
+    let contents = match read_to_string(path) {
+        Ok(v) => v,
+        Err(e) => {
+            report::error(
+                path,
+                e.kind(),
+                format!("Error reading {}: {}", path.display(), e),
+            );
+            return Vec::new();
+        }
+    };
+    parse_yaml_list(&contents, key)
+}
+
+fn parse_yaml_list(contents: &str, key: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut in_list = false;
+    for line in contents.lines() {
+        let line = line.split(" #").next().unwrap_or_default().trim_end();
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        if !line.starts_with(' ') && !line.starts_with('-') {
+            in_list = line.strip_suffix(':') == Some(key);
+            continue;
+        }
+        if let (true, Some(item)) = (in_list, line.trim_start().strip_prefix('-')) {
+            items.push(item.trim().trim_matches(['\'', '"']).to_owned());
+        }
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::tree;
+
+    /// Runs the detector called `name` on `dir` in a tree of `files`, returning the projects it
+    /// found along with their artifact directories relative to the tree
+    fn detect(name: &str, files: &[(&str, &str)], dir: &str) -> Vec<(String, Vec<String>)> {
+        let tree = tree(files);
+        let root = tree.path().canonicalize().unwrap();
+        let dir = root.join(dir);
+        let listing = Listing::read(&dir).unwrap();
+        let detector = select(&[name.to_owned()], &[]).unwrap()[0];
+        let (detections, _) = report::capture(|| detector.detect(&dir, &listing));
+        detections
+            .into_iter()
+            .map(|detection| {
+                let artifacts = detection
+                    .artifacts
+                    .iter()
+                    .map(|artifact| {
+                        let path = artifact.path.strip_prefix(&root).unwrap();
+                        path.to_string_lossy().into_owned()
+                    })
+                    .collect();
+                (detection.project.describe(), artifacts)
+            })
+            .collect()
+    }
+
+    fn found(projects: &[(&str, &[&str])]) -> Vec<(String, Vec<String>)> {
+        projects
+            .iter()
+            .map(|(project, artifacts)| {
+                let artifacts = artifacts.iter().map(|path| path.to_string()).collect();
+                (project.to_string(), artifacts)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn selects_detectors_by_name() {
+        let names = |detectors: Vec<&dyn Detector>| {
+            detectors
+                .iter()
+                .map(|detector| detector.name())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(select(&[], &[]).unwrap()).len(), BUILTIN.len());
+        let enable = ["zig".to_owned(), "cargo".to_owned()];
+        assert_eq!(names(select(&enable, &[]).unwrap()), ["zig", "cargo"]);
+        let disable = ["cargo".to_owned()];
+        assert_eq!(names(select(&enable, &disable).unwrap()), ["zig"]);
+        let error = select(&[], &["rust".to_owned()]).unwrap_err();
+        assert!(error.starts_with("there is no detector named \"rust\""));
+    }
+
+    #[test]
+    fn detects_cargo_workspaces() {
+        let files = [
+            ("w/Cargo.toml", "[workspace]\nmembers = [\"m\"]\n"),
+            ("w/target/", ""),
+            (
+                "w/m/Cargo.toml",
+                "[package]\nname = \"m\"\nversion = \"0.1.0\"\n",
+            ),
+            ("w/m/target/", ""),
+        ];
+        assert_eq!(
+            detect("cargo", &files, "w"),
+            found(&[
+                ("workspace w", &["w/target"]),
+                ("workspace member m", &["w/m/target"]),
+            ])
+        );
+    }
+
+    #[test]
+    fn detects_node_projects() {
+        let files = [
+            ("web/package.json", r#"{"name": "web"}"#),
+            ("web/package-lock.json", ""),
+            ("web/node_modules/", ""),
+            ("web/.next/", ""),
+        ];
+        assert_eq!(
+            detect("node", &files, "web"),
+            found(&[("package web", &["web/node_modules", "web/.next"])])
+        );
+    }
+
+    #[test]
+    fn detects_python_projects() {
+        let files = [
+            ("py/pyproject.toml", "[project]\nname = \"py\"\n"),
+            ("py/.venv/pyvenv.cfg", ""),
+            ("py/venv/", ""),
+            ("py/.pytest_cache/", ""),
+            ("py/build/", ""),
+            ("py/src/py.egg-info/", ""),
+            ("py/src/py/__pycache__/", ""),
+        ];
+        // A venv without a pyvenv.cfg could be anything
+        assert_eq!(
+            detect("python", &files, "py"),
+            found(&[(
+                "package py",
+                &[
+                    "py/.venv",
+                    "py/.pytest_cache",
+                    "py/build",
+                    "py/src/py.egg-info"
+                ],
+            )])
+        );
+        // Caches in the sources are found from the directory they are in
+        assert_eq!(
+            detect("python", &files, "py/src/py"),
+            found(&[("package py", &["py/src/py/__pycache__"])])
+        );
+    }
+
+    #[test]
+    fn detects_gradle_builds() {
+        let files = [
+            (
+                "g/settings.gradle",
+                "rootProject.name = 'app'\ninclude ':lib'\n",
+            ),
+            ("g/build.gradle", ""),
+            ("g/build/", ""),
+            ("g/.gradle/", ""),
+            ("g/lib/build.gradle", ""),
+            ("g/lib/build/", ""),
+        ];
+        assert_eq!(
+            detect("gradle", &files, "g"),
+            found(&[
+                ("workspace app", &["g/build", "g/.gradle"]),
+                ("workspace member lib", &["g/lib/build"]),
+            ])
+        );
+    }
+
+    #[test]
+    fn detects_maven_builds() {
+        let pom = "<project><artifactId>app</artifactId><modules><module>core</module></modules></project>";
+        let files = [
+            ("mvn/pom.xml", pom),
+            ("mvn/target/", ""),
+            (
+                "mvn/core/pom.xml",
+                "<project><artifactId>app-core</artifactId></project>",
+            ),
+            ("mvn/core/target/", ""),
+        ];
+        assert_eq!(
+            detect("maven", &files, "mvn"),
+            found(&[
+                ("workspace app", &["mvn/target"]),
+                ("workspace member app-core", &["mvn/core/target"]),
+            ])
+        );
+    }
+
+    #[test]
+    fn detects_sbt_builds() {
+        let files = [
+            (
+                "s/build.sbt",
+                "lazy val core = project.in(file(\"core\"))\n",
+            ),
+            ("s/target/", ""),
+            ("s/project/target/", ""),
+            ("s/core/target/", ""),
+        ];
+        assert_eq!(
+            detect("sbt", &files, "s"),
+            found(&[
+                ("workspace s", &["s/target", "s/project/target"]),
+                ("workspace member core", &["s/core/target"]),
+            ])
+        );
+    }
+
+    #[test]
+    fn detects_cmake_build_directories() {
+        let tree = tree(&[("src/CMakeLists.txt", ""), ("build/CMakeCache.txt", "")]);
+        let root = tree.path().canonicalize().unwrap();
+        let cache = format!(
+            "CMAKE_HOME_DIRECTORY:INTERNAL={}\nCMAKE_PROJECT_NAME:STATIC=app\n",
+            root.join("src").display()
+        );
+        std::fs::write(root.join("build/CMakeCache.txt"), cache).unwrap();
+        let listing = Listing::read(&root.join("build")).unwrap();
+        let detections = find("cmake").unwrap().detect(&root.join("build"), &listing);
+        assert_eq!(detections.len(), 1);
+        assert_eq!(detections[0].project.describe(), "package app");
+        assert_eq!(detections[0].project.dir, root.join("src"));
+        assert_eq!(detections[0].artifacts[0].path, root.join("build"));
+    }
+
+    #[test]
+    fn detects_meson_build_directories() {
+        let tree = tree(&[
+            ("src/meson.build", ""),
+            ("build/meson-private/", ""),
+            (
+                "build/meson-info/intro-projectinfo.json",
+                r#"{"descriptive_name": "app"}"#,
+            ),
+        ]);
+        let root = tree.path().canonicalize().unwrap();
+        let info = serde_json::json!({"directories": {"source": root.join("src")}});
+        std::fs::write(
+            root.join("build/meson-info/meson-info.json"),
+            info.to_string(),
+        )
+        .unwrap();
+        let listing = Listing::read(&root.join("build")).unwrap();
+        let detections = find("meson").unwrap().detect(&root.join("build"), &listing);
+        assert_eq!(detections.len(), 1);
+        assert_eq!(detections[0].project.describe(), "package app");
+        assert_eq!(detections[0].project.dir, root.join("src"));
+        assert_eq!(detections[0].artifacts[0].path, root.join("build"));
+    }
+
+    #[test]
+    fn detects_ninja_build_directories() {
+        let files = [
+            ("out/build.ninja", "build build.ninja: gn\n"),
+            ("out/.ninja_log", ""),
+        ];
+        assert_eq!(
+            detect("ninja", &files, "out"),
+            found(&[("package out", &["out"])])
+        );
+    }
+
+    #[test]
+    fn detects_haskell_projects() {
+        let files = [
+            ("hs/stack.yaml", "packages:\n- .\n- lib\n"),
+            ("hs/app.cabal", ""),
+            ("hs/.stack-work/", ""),
+            ("hs/dist-newstyle/", ""),
+            ("hs/lib/.stack-work/", ""),
+        ];
+        assert_eq!(
+            detect("haskell", &files, "hs"),
+            found(&[
+                ("workspace app", &["hs/dist-newstyle", "hs/.stack-work"]),
+                ("workspace member lib", &["hs/lib/.stack-work"]),
+            ])
+        );
+    }
+
+    #[test]
+    fn detects_elixir_projects() {
+        let files = [
+            (
+                "ex/mix.exs",
+                "def project do\n  [app: :my_app, version: \"0.1.0\"]\nend\n",
+            ),
+            ("ex/_build/", ""),
+            ("ex/deps/", ""),
+        ];
+        assert_eq!(
+            detect("elixir", &files, "ex"),
+            found(&[("package my_app", &["ex/_build", "ex/deps"])])
+        );
+    }
+
+    #[test]
+    fn detects_ocaml_projects() {
+        let files = [
+            ("ml/dune-project", "(lang dune 3.0)\n(name parser)\n"),
+            ("ml/_build/", ""),
+        ];
+        assert_eq!(
+            detect("ocaml", &files, "ml"),
+            found(&[("package parser", &["ml/_build"])])
+        );
+    }
+
+    #[test]
+    fn detects_zig_projects() {
+        let files = [
+            ("z/build.zig", ""),
+            ("z/.zig-cache/", ""),
+            ("z/zig-out/", ""),
+        ];
+        assert_eq!(
+            detect("zig", &files, "z"),
+            found(&[("package z", &["z/.zig-cache", "z/zig-out"])])
+        );
+    }
+
+    #[test]
+    fn parses_yaml_lists() {
+        let yaml = r#"
+# packages: [commented]
+packages:
+  - 'apps/*'
+  - "libs/**" # everything below libs
+  -   tools
+  - '!**/test/**'
+
+catalog:
+  - other
+nested:
+  packages:
+    - deeper
+"#;
+        assert_eq!(
+            parse_yaml_list(yaml, "packages"),
+            ["apps/*", "libs/**", "tools", "!**/test/**"]
+        );
+        assert_eq!(parse_yaml_list(yaml, "catalog"), ["other"]);
+        assert!(parse_yaml_list(yaml, "missing").is_empty());
+    }
+
+    #[test]
+    fn parses_unindented_yaml_lists() {
+        let yaml = "resolver: lts-22.0\npackages:\n- .\n- lib\nextra-deps: []\n";
+        assert_eq!(parse_yaml_list(yaml, "packages"), [".", "lib"]);
+        assert!(parse_yaml_list(yaml, "resolver").is_empty());
+    }
+}
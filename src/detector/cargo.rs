@@ -0,0 +1,84 @@
+//! Cargo packages and workspaces, whose target directory can be moved by cargo's config files
+use std::path::{Path, PathBuf};
+
+use super::{project_dir, Artifact, Detection, Detector, Listing};
+use crate::cargo_config::resolve_target_dir;
+use crate::dir_key;
+use crate::manifest::{read_project, workspace_members, ProjectKind};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cargo;
+impl Detector for Cargo {
+    fn name(&self) -> &'static str {
+        "cargo"
+    }
+    /// Cargo projects rarely contain other projects, and skipping them saves walking big trees
+    fn stops_descent(&self) -> bool {
+        true
+    }
+    fn detect(&self, dir: &Path, listing: &Listing) -> Vec<Detection> {
+        if !listing.contains("Cargo.toml") {
+            return Vec::new();
+        }
+        let project_dir = project_dir(dir);
+        let project = read_project(&project_dir);
+        let artifact = match &project.kind {
+            ProjectKind::Member(workspace_root) => stray_target_dir(&project_dir, workspace_root)
+                .map(|path| Artifact { path, stray: true }),
+            _ => {
+                let path = resolve_target_dir(&project_dir);
+                path.is_dir().then_some(Artifact { path, stray: false })
+            }
+        };
+        let is_workspace = project.kind == ProjectKind::Workspace;
+        let mut detections = vec![Detection {
+            project,
+            artifacts: artifact.into_iter().collect(),
+            activity: vec![project_dir.clone()],
+            nested: false,
+        }];
+        if is_workspace {
+            for member_dir in workspace_members(&project_dir) {
+                if let Some(path) = stray_target_dir(&member_dir, &project_dir) {
+                    detections.push(Detection {
+                        project: read_project(&member_dir),
+                        artifacts: vec![Artifact { path, stray: true }],
+                        activity: vec![member_dir],
+                        nested: true,
+                    });
+                }
+            }
+        }
+        detections
+    }
+}
+
+/// Returns the target directory of a workspace member if it differs from the workspace's
+///
+/// Cargo always builds members into the workspace target directory, so these are left over from
+/// running cargo with a manifest path that bypassed the workspace
+pub fn stray_target_dir(member_dir: &Path, workspace_root: &Path) -> Option<PathBuf> {
+    let target_path = resolve_target_dir(member_dir);
+    if !target_path.is_dir() {
+        return None;
+    }
+    let workspace_target = resolve_target_dir(workspace_root);
+    match (dir_key(&target_path), dir_key(&workspace_target)) {
+        (Ok(a), Ok(b)) if a == b => None,
+        _ => Some(target_path),
+    }
+}
+
+/// Returns whether `path` is the target directory of a Cargo project in `dir`
+///
+/// Maven and sbt build into a directory named `target` as well, so they leave it to cargo when a
+/// directory has both manifests and cargo builds there
+pub fn is_cargo_target(dir: &Path, path: &Path) -> bool {
+    if !dir.join("Cargo.toml").is_file() {
+        return false;
+    }
+    match (dir_key(&resolve_target_dir(dir)), dir_key(path)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
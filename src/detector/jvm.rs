@@ -0,0 +1,486 @@
+//! Gradle, Maven and sbt builds, along with the subprojects and modules they include
+//!
+//! Build files of these are programs or XML, so only the simple, common ways of declaring
+//! subprojects are read. Maven and sbt build into `target` like cargo does, which is left to
+//! [`Cargo`](super::Cargo) when a directory has a `Cargo.toml` too
+use std::collections::VecDeque;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use super::cargo::is_cargo_target;
+use super::{project_dir, Artifact, Detection, Detector, Listing};
+use crate::manifest::{dir_name, Project, ProjectKind};
+use crate::report;
+
+const GRADLE_BUILD_FILES: [&str; 2] = ["build.gradle", "build.gradle.kts"];
+const GRADLE_SETTINGS_FILES: [&str; 2] = ["settings.gradle", "settings.gradle.kts"];
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Gradle;
+impl Detector for Gradle {
+    fn name(&self) -> &'static str {
+        "gradle"
+    }
+    fn detect(&self, dir: &Path, listing: &Listing) -> Vec<Detection> {
+        let settings_file = GRADLE_SETTINGS_FILES
+            .into_iter()
+            .find(|name| listing.contains(name));
+        if settings_file.is_none() && !GRADLE_BUILD_FILES.iter().any(|name| listing.contains(name))
+        {
+            return Vec::new();
+        }
+        let project_dir = project_dir(dir);
+        let settings = settings_file.and_then(|name| read_build_file(&project_dir.join(name)));
+        let name = settings
+            .as_deref()
+            .and_then(gradle_root_project_name)
+            .unwrap_or_else(|| dir_name(&project_dir));
+        let mut members: Vec<_> = settings
+            .as_deref()
+            .map(gradle_includes)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|path| project_dir.join(path))
+            .filter(|dir| is_gradle_project(dir))
+            .collect();
+        // buildSrc is built as part of every build without being included
+        if listing.has_dir("buildSrc") {
+            members.push(project_dir.join("buildSrc"));
+        }
+        let mut detections = vec![Detection {
+            project: Project {
+                name,
+                kind: if members.is_empty() {
+                    ProjectKind::Package
+                } else {
+                    ProjectKind::Workspace
+                },
+                dir: project_dir.clone(),
+                settings: None,
+            },
+            artifacts: ["build", ".gradle"]
+                .into_iter()
+                .filter(|name| listing.has_dir(name))
+                .map(|name| artifact(project_dir.join(name)))
+                .collect(),
+            activity: vec![project_dir.clone()],
+            nested: false,
+        }];
+        detections.extend(members.into_iter().filter_map(|member_dir| {
+            let build = member_dir.join("build");
+            build
+                .is_dir()
+                .then(|| member(&project_dir, member_dir, vec![artifact(build)]))
+        }));
+        detections
+    }
+}
+
+fn is_gradle_project(dir: &Path) -> bool {
+    GRADLE_BUILD_FILES
+        .iter()
+        .any(|name| dir.join(name).is_file())
+}
+
+/// Reads `rootProject.name` from a settings file
+fn gradle_root_project_name(settings: &str) -> Option<String> {
+    settings.lines().find_map(|line| {
+        let value = line.trim().strip_prefix("rootProject.name")?;
+        quoted_strings(value.trim_start().strip_prefix('=')?)
+            .next()
+            .map(str::to_owned)
+    })
+}
+
+/// Returns the directories of the subprojects a settings file includes, relative to the root
+///
+/// Project paths like `:libs:core` map to directories like `libs/core`, as they do unless the
+/// settings file moves them. An `include` carries on over the following lines while its
+/// parentheses are open or its line ends with a comma
+fn gradle_includes(settings: &str) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let mut depth = 0;
+    let mut continued = false;
+    for line in settings.lines() {
+        let line = line.split("//").next().unwrap_or_default().trim();
+        let arguments = match line.strip_prefix("include") {
+            Some(rest) if rest.starts_with([' ', '(']) => {
+                depth = 0;
+                rest
+            }
+            _ if continued => line,
+            _ => continue,
+        };
+        paths.extend(
+            quoted_strings(arguments)
+                .map(|path| path.trim_start_matches(':').split(':').collect::<PathBuf>()),
+        );
+        depth += arguments.matches('(').count() as isize;
+        depth -= arguments.matches(')').count() as isize;
+        continued = depth > 0 || arguments.ends_with(',');
+    }
+    paths
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Maven;
+impl Detector for Maven {
+    fn name(&self) -> &'static str {
+        "maven"
+    }
+    fn detect(&self, dir: &Path, listing: &Listing) -> Vec<Detection> {
+        if !listing.contains("pom.xml") {
+            return Vec::new();
+        }
+        let project_dir = project_dir(dir);
+        let pom = read_build_file(&project_dir.join("pom.xml")).map(|pom| strip_xml_comments(&pom));
+        let modules = pom.as_deref().map(maven_modules).unwrap_or_default();
+        let mut detections = vec![Detection {
+            project: Project {
+                name: pom
+                    .as_deref()
+                    .and_then(maven_artifact_id)
+                    .unwrap_or_else(|| dir_name(&project_dir)),
+                kind: if modules.is_empty() {
+                    ProjectKind::Package
+                } else {
+                    ProjectKind::Workspace
+                },
+                dir: project_dir.clone(),
+                settings: None,
+            },
+            artifacts: target_artifact(&project_dir, listing.has_dir("target"))
+                .into_iter()
+                .collect(),
+            activity: vec![project_dir.clone()],
+            nested: false,
+        }];
+        // Modules can be aggregators of their own modules
+        let mut pending: VecDeque<_> = modules
+            .into_iter()
+            .map(|module| project_dir.join(module))
+            .collect();
+        let mut seen = Vec::new();
+        while let Some(module_dir) = pending.pop_front() {
+            if seen.contains(&module_dir) {
+                continue;
+            }
+            seen.push(module_dir.clone());
+            let Some(pom) = module_dir
+                .join("pom.xml")
+                .is_file()
+                .then(|| read_build_file(&module_dir.join("pom.xml")))
+                .flatten()
+                .map(|pom| strip_xml_comments(&pom))
+            else {
+                continue;
+            };
+            pending.extend(
+                maven_modules(&pom)
+                    .into_iter()
+                    .map(|module| module_dir.join(module)),
+            );
+            if let Some(target) = target_artifact(&module_dir, module_dir.join("target").is_dir()) {
+                let mut detection = member(&project_dir, module_dir, vec![target]);
+                if let Some(name) = maven_artifact_id(&pom) {
+                    detection.project.name = name;
+                }
+                detections.push(detection);
+            }
+        }
+        detections
+    }
+}
+
+/// Returns the module directories listed in a POM
+fn maven_modules(pom: &str) -> Vec<PathBuf> {
+    tag_values(pom, "module").map(PathBuf::from).collect()
+}
+
+/// Returns the project's own `artifactId`, rather than its parent's
+fn maven_artifact_id(pom: &str) -> Option<String> {
+    let pom = match (pom.find("<parent>"), pom.find("</parent>")) {
+        (Some(start), Some(end)) if start < end => format!("{}{}", &pom[..start], &pom[end..]),
+        _ => pom.to_owned(),
+    };
+    let artifact_id = tag_values(&pom, "artifactId").next().map(str::to_owned);
+    artifact_id
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sbt;
+impl Detector for Sbt {
+    fn name(&self) -> &'static str {
+        "sbt"
+    }
+    fn detect(&self, dir: &Path, listing: &Listing) -> Vec<Detection> {
+        if !listing.contains("build.sbt") {
+            return Vec::new();
+        }
+        let project_dir = project_dir(dir);
+        let build = read_build_file(&project_dir.join("build.sbt"));
+        let subprojects: Vec<_> = build
+            .as_deref()
+            .map(sbt_subprojects)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|path| project_dir.join(path))
+            .filter(|dir| dir != &project_dir)
+            .collect();
+        let mut artifacts: Vec<_> = target_artifact(&project_dir, listing.has_dir("target"))
+            .into_iter()
+            .collect();
+        // The build definition is a project of its own, built into `project/target`
+        let meta_target = project_dir.join("project").join("target");
+        if meta_target.is_dir() {
+            artifacts.push(artifact(meta_target));
+        }
+        if listing.has_dir(".bloop") {
+            artifacts.push(artifact(project_dir.join(".bloop")));
+        }
+        let mut detections = vec![Detection {
+            project: Project {
+                name: dir_name(&project_dir),
+                kind: if subprojects.is_empty() {
+                    ProjectKind::Package
+                } else {
+                    ProjectKind::Workspace
+                },
+                dir: project_dir.clone(),
+                settings: None,
+            },
+            artifacts,
+            activity: vec![project_dir.clone()],
+            nested: false,
+        }];
+        detections.extend(subprojects.into_iter().filter_map(|subproject_dir| {
+            let target = target_artifact(&subproject_dir, subproject_dir.join("target").is_dir())?;
+            Some(member(&project_dir, subproject_dir, vec![target]))
+        }));
+        detections
+    }
+}
+
+/// Returns the directories of the subprojects defined with `project.in(file(...))` or
+/// `project in file(...)`, relative to the root
+///
+/// Only literal paths count, as anything else would need the build to be evaluated
+fn sbt_subprojects(build: &str) -> Vec<PathBuf> {
+    let mut subprojects = Vec::new();
+    for marker in [".in(file(", " in file("] {
+        for (start, _) in build.match_indices(marker) {
+            let rest = build[start + marker.len()..].trim_start();
+            if !rest.starts_with(['"', '\'']) {
+                continue;
+            }
+            if let Some(path) = quoted_strings(rest).next() {
+                let path = PathBuf::from(path);
+                if !subprojects.contains(&path) {
+                    subprojects.push(path);
+                }
+            }
+        }
+    }
+    subprojects
+}
+
+/// Returns the `target` directory of a Maven or sbt project if it exists and isn't cargo's
+fn target_artifact(dir: &Path, exists: bool) -> Option<Artifact> {
+    let path = dir.join("target");
+    (exists && !is_cargo_target(dir, &path)).then(|| artifact(path))
+}
+
+fn artifact(path: PathBuf) -> Artifact {
+    Artifact { path, stray: false }
+}
+
+/// A subproject or module of the build rooted at `root`
+fn member(root: &Path, dir: PathBuf, artifacts: Vec<Artifact>) -> Detection {
+    Detection {
+        project: Project {
+            name: dir_name(&dir),
+            kind: ProjectKind::Member(root.to_path_buf()),
+            dir: dir.clone(),
+            settings: None,
+        },
+        artifacts,
+        activity: vec![dir],
+        nested: true,
+    }
+}
+
+fn read_build_file(path: &Path) -> Option<String> {
+    match read_to_string(path) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            report::error(
+                path,
+                e.kind(),
+                format!("Error reading manifest {}: {}", path.display(), e),
+            );
+            None
+        }
+    }
+}
+
+/// Returns the contents of the strings quoted with `"` or `'` in a line of a build file, up to a
+/// comment
+fn quoted_strings(line: &str) -> impl Iterator<Item = &str> {
+    let line = line.split("//").next().unwrap_or_default();
+    let mut rest = line;
+    std::iter::from_fn(move || {
+        let start = rest.find(['"', '\''])?;
+        let quote = rest[start..].chars().next()?;
+        let after = &rest[start + 1..];
+        let end = after.find(quote)?;
+        rest = &after[end + 1..];
+        Some(&after[..end])
+    })
+}
+
+fn strip_xml_comments(xml: &str) -> String {
+    let mut stripped = String::with_capacity(xml.len());
+    let mut rest = xml;
+    while let Some(start) = rest.find("<!--") {
+        stripped.push_str(&rest[..start]);
+        rest = match rest[start..].find("-->") {
+            Some(end) => &rest[start + end + 3..],
+            None => "",
+        };
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// Returns the trimmed text of every `<tag>` element, which must not have attributes
+fn tag_values<'a>(xml: &'a str, tag: &str) -> impl Iterator<Item = &'a str> {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+    let mut rest = xml;
+    std::iter::from_fn(move || {
+        let start = rest.find(&open)? + open.len();
+        let end = rest[start..].find(&close)? + start;
+        let value = rest[start..end].trim();
+        rest = &rest[end + close.len()..];
+        Some(value)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn reads_gradle_root_project_name() {
+        let settings = "pluginManagement {}\nrootProject.name = 'shop'\ninclude ':app'\n";
+        assert_eq!(gradle_root_project_name(settings).as_deref(), Some("shop"));
+        let settings = "  rootProject.name=\"shop\" // the old name was \"store\"";
+        assert_eq!(gradle_root_project_name(settings).as_deref(), Some("shop"));
+        assert_eq!(
+            gradle_root_project_name("rootProject.buildFileName = 'x'"),
+            None
+        );
+        assert_eq!(gradle_root_project_name("include ':app'"), None);
+    }
+
+    #[test]
+    fn reads_gradle_includes() {
+        let settings = r#"
+rootProject.name = "shop"
+include ':app', ':libs:core'
+include("web")
+includeBuild("build-logic")
+// include ':commented'
+include(
+    ":api",
+    ":cli", // the command line tool
+)
+include ':a',
+    ':b'
+println("not included")
+"#;
+        assert_eq!(
+            gradle_includes(settings),
+            paths(&["app", "libs/core", "web", "api", "cli", "a", "b"])
+        );
+    }
+
+    #[test]
+    fn reads_maven_modules() {
+        let pom = strip_xml_comments(
+            r#"<project>
+  <modules>
+    <module>core</module>
+    <!-- <module>old</module> -->
+    <module> web/app </module>
+  </modules>
+</project>"#,
+        );
+        assert_eq!(maven_modules(&pom), paths(&["core", "web/app"]));
+        assert!(maven_modules("<project></project>").is_empty());
+    }
+
+    #[test]
+    fn reads_maven_artifact_id() {
+        let pom = r#"<project>
+  <parent>
+    <groupId>org.example</groupId>
+    <artifactId>parent</artifactId>
+  </parent>
+  <artifactId>core</artifactId>
+  <dependencies>
+    <dependency><artifactId>junit</artifactId></dependency>
+  </dependencies>
+</project>"#;
+        assert_eq!(maven_artifact_id(pom).as_deref(), Some("core"));
+        let pom = "<project><artifactId>app</artifactId></project>";
+        assert_eq!(maven_artifact_id(pom).as_deref(), Some("app"));
+        let pom = "<project><parent><artifactId>parent</artifactId></parent></project>";
+        assert_eq!(maven_artifact_id(pom), None);
+    }
+
+    #[test]
+    fn strips_xml_comments() {
+        assert_eq!(strip_xml_comments("a<!-- b -->c<!--d-->e"), "ace");
+        assert_eq!(strip_xml_comments("a<!-- <x> -- y -->b"), "ab");
+        // An unterminated comment runs to the end
+        assert_eq!(strip_xml_comments("a<!-- b"), "a");
+        assert_eq!(strip_xml_comments("no comments"), "no comments");
+    }
+
+    #[test]
+    fn reads_tag_values() {
+        let xml = "<a>1</a><ab>2</ab><a>\n  3\n</a><a>4";
+        assert_eq!(tag_values(xml, "a").collect::<Vec<_>>(), ["1", "3"]);
+        assert_eq!(tag_values(xml, "ab").collect::<Vec<_>>(), ["2"]);
+        assert_eq!(tag_values(xml, "b").count(), 0);
+    }
+
+    #[test]
+    fn reads_quoted_strings() {
+        let strings = |line| quoted_strings(line).collect::<Vec<_>>();
+        assert_eq!(strings(r#"include ':a', "b""#), [":a", "b"]);
+        assert_eq!(strings(r#"'it"s', "it's""#), [r#"it"s"#, "it's"]);
+        assert_eq!(strings("'a' // 'b'"), ["a"]);
+        assert_eq!(strings("'unterminated"), Vec::<&str>::new());
+        assert_eq!(strings("no strings"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn reads_sbt_subprojects() {
+        let build = r#"
+lazy val root = (project in file("."))
+  .aggregate(core, web)
+lazy val core = project.in(file("modules/core"))
+lazy val web = (project in file( "web" ))
+lazy val again = project.in(file("modules/core"))
+lazy val generated = project.in(file(baseDir))
+  .settings(name := "generated")
+"#;
+        assert_eq!(sbt_subprojects(build), paths(&["modules/core", ".", "web"]));
+    }
+}
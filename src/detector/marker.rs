@@ -0,0 +1,164 @@
+//! Haskell, Elixir, OCaml and Zig projects, which are recognized by a single manifest and build
+//! into directories with fixed names next to it
+use std::fs::read_to_string;
+use std::path::Path;
+
+use super::{project_dir, yaml_list, Artifact, Detection, Detector, Listing};
+use crate::manifest::{dir_name, Project, ProjectKind};
+use crate::report;
+
+/// Cabal and Stack projects, along with the packages a `stack.yaml` lists, which Stack builds
+/// into a `.stack-work` of their own
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Haskell;
+impl Detector for Haskell {
+    fn name(&self) -> &'static str {
+        "haskell"
+    }
+    fn detect(&self, dir: &Path, listing: &Listing) -> Vec<Detection> {
+        let cabal_file = listing.names.iter().map(Path::new).find(|name| {
+            name.extension()
+                .is_some_and(|extension| extension == "cabal")
+        });
+        let is_stack = listing.contains("stack.yaml");
+        if cabal_file.is_none() && !is_stack && !listing.contains("cabal.project") {
+            return Vec::new();
+        }
+        let mut detections = detect(dir, listing, &["dist-newstyle", ".stack-work"], |_| {
+            cabal_file.and_then(|name| Some(name.file_stem()?.to_string_lossy().into_owned()))
+        });
+        if let (true, Some(root)) = (is_stack, detections.first()) {
+            let root = root.project.dir.clone();
+            for package in yaml_list(&root.join("stack.yaml"), "packages") {
+                let package_dir = root.join(&package);
+                let stack_work = package_dir.join(".stack-work");
+                if package_dir == root || package == "." || !stack_work.is_dir() {
+                    continue;
+                }
+                detections[0].project.kind = ProjectKind::Workspace;
+                detections.push(Detection {
+                    project: Project {
+                        name: dir_name(&package_dir),
+                        kind: ProjectKind::Member(root.clone()),
+                        dir: package_dir.clone(),
+                        settings: None,
+                    },
+                    artifacts: vec![Artifact {
+                        path: stack_work,
+                        stray: false,
+                    }],
+                    activity: vec![package_dir],
+                    nested: true,
+                });
+            }
+        }
+        detections
+    }
+}
+
+/// Mix projects, whose `deps` holds fetched dependencies that `mix deps.get` brings back
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Elixir;
+impl Detector for Elixir {
+    fn name(&self) -> &'static str {
+        "elixir"
+    }
+    fn detect(&self, dir: &Path, listing: &Listing) -> Vec<Detection> {
+        if !listing.contains("mix.exs") {
+            return Vec::new();
+        }
+        detect(dir, listing, &["_build", "deps"], |project_dir| {
+            // The app name is an atom, as in `app: :my_app`
+            let mix = read_manifest(&project_dir.join("mix.exs"))?;
+            let start = mix.find("app:")? + "app:".len();
+            let app = mix[start..].trim_start().strip_prefix(':')?;
+            let end = app
+                .find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(app.len());
+            Some(app[..end].to_owned()).filter(|app| !app.is_empty())
+        })
+    }
+}
+
+/// Dune projects
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ocaml;
+impl Detector for Ocaml {
+    fn name(&self) -> &'static str {
+        "ocaml"
+    }
+    fn detect(&self, dir: &Path, listing: &Listing) -> Vec<Detection> {
+        if !listing.contains("dune-project") {
+            return Vec::new();
+        }
+        detect(dir, listing, &["_build"], |project_dir| {
+            let dune_project = read_manifest(&project_dir.join("dune-project"))?;
+            let start = dune_project.find("(name ")? + "(name ".len();
+            let name = dune_project[start..].split(')').next()?.trim();
+            Some(name.to_owned()).filter(|name| !name.is_empty())
+        })
+    }
+}
+
+/// Zig projects, whose cache moved from `zig-cache` to `.zig-cache` in Zig 0.13
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Zig;
+impl Detector for Zig {
+    fn name(&self) -> &'static str {
+        "zig"
+    }
+    fn detect(&self, dir: &Path, listing: &Listing) -> Vec<Detection> {
+        if !listing.contains("build.zig") {
+            return Vec::new();
+        }
+        detect(
+            dir,
+            listing,
+            &["zig-cache", ".zig-cache", "zig-out"],
+            |_| None,
+        )
+    }
+}
+
+/// Returns the project in `dir` with whichever of `artifact_dirs` exist, named by `name` or
+/// after the directory
+fn detect(
+    dir: &Path,
+    listing: &Listing,
+    artifact_dirs: &[&str],
+    name: impl FnOnce(&Path) -> Option<String>,
+) -> Vec<Detection> {
+    let project_dir = project_dir(dir);
+    vec![Detection {
+        project: Project {
+            name: name(&project_dir).unwrap_or_else(|| dir_name(&project_dir)),
+            kind: ProjectKind::Package,
+            dir: project_dir.clone(),
+            settings: None,
+        },
+        artifacts: artifact_dirs
+            .iter()
+            .filter(|name| listing.has_dir(name))
+            .map(|name| Artifact {
+                path: project_dir.join(name),
+                stray: false,
+            })
+            .collect(),
+        activity: vec![project_dir],
+        nested: false,
+    }]
+}
+
+fn read_manifest(path: &Path) -> Option<String> {
+    match read_to_string(path) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            report::error(
+                path,
+                e.kind(),
+                format!("Error reading manifest {}: {}", path.display(), e),
+            );
+            None
+        }
+    }
+}
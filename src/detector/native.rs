@@ -0,0 +1,388 @@
+//! Build directories of C and C++ projects, recognized by what CMake, Meson and Ninja leave in
+//! them rather than by their name, which is up to whoever configured the build
+//!
+//! The directory being looked at is the build directory itself, and the sources are wherever the
+//! build was configured from. Builds configured inside the source directory, or whose sources
+//! can't be found anymore, are never cleaned, as that could delete sources too
+use serde_json::Value;
+use std::fs::read_to_string;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use super::{project_dir, Artifact, Detection, Detector, Listing};
+use crate::manifest::{dir_name, Project, ProjectKind};
+use crate::report;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Cmake;
+impl Detector for Cmake {
+    fn name(&self) -> &'static str {
+        "cmake"
+    }
+    fn detect(&self, dir: &Path, listing: &Listing) -> Vec<Detection> {
+        if !listing.contains("CMakeCache.txt") {
+            return Vec::new();
+        }
+        let build_dir = project_dir(dir);
+        if listing.contains("CMakeLists.txt") {
+            return in_source_build(&build_dir);
+        }
+        let cache = read_file(&build_dir.join("CMakeCache.txt")).unwrap_or_default();
+        let entry = |key: &str| {
+            cache.lines().find_map(|line| {
+                let (name, value) = line.split_once('=')?;
+                (name.split(':').next() == Some(key)).then(|| value.trim().to_owned())
+            })
+        };
+        let source_dir = entry("CMAKE_HOME_DIRECTORY").map(PathBuf::from);
+        let Some(source_dir) = existing_source_dir(&build_dir, source_dir) else {
+            return Vec::new();
+        };
+        build_detection(build_dir, Some(source_dir), entry("CMAKE_PROJECT_NAME"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Meson;
+impl Detector for Meson {
+    fn name(&self) -> &'static str {
+        "meson"
+    }
+    fn detect(&self, dir: &Path, listing: &Listing) -> Vec<Detection> {
+        if !listing.has_dir("meson-private") {
+            return Vec::new();
+        }
+        let build_dir = project_dir(dir);
+        if listing.contains("meson.build") {
+            return in_source_build(&build_dir);
+        }
+        let info_dir = build_dir.join("meson-info");
+        let read_info = |name: &str| {
+            let path = info_dir.join(name);
+            let contents = read_file(&path)?;
+            match serde_json::from_str::<Value>(&contents) {
+                Ok(v) => Some(v),
+                Err(e) => {
+                    report::error(
+                        &path,
+                        ErrorKind::InvalidData,
+                        format!("Error parsing {}: {}", path.display(), e),
+                    );
+                    None
+                }
+            }
+        };
+        // Builds that were never fully configured have no introspection files yet
+        let (source_dir, name) = if info_dir.is_dir() {
+            let source_dir = read_info("meson-info.json").and_then(|info| {
+                Some(PathBuf::from(
+                    info.get("directories")?.get("source")?.as_str()?,
+                ))
+            });
+            let name = read_info("intro-projectinfo.json")
+                .and_then(|info| Some(info.get("descriptive_name")?.as_str()?.to_owned()));
+            (source_dir, name)
+        } else {
+            (None, None)
+        };
+        let Some(source_dir) = existing_source_dir(&build_dir, source_dir) else {
+            return Vec::new();
+        };
+        build_detection(build_dir, Some(source_dir), name)
+    }
+}
+
+/// Files that only exist in source directories, which a build directory never contains
+const SOURCE_MANIFESTS: [&str; 13] = [
+    ".git",
+    ".gn",
+    "BUILD",
+    "BUILD.gn",
+    "CMakeLists.txt",
+    "Cargo.toml",
+    "Makefile",
+    "configure",
+    "configure.py",
+    "meson.build",
+    "package.json",
+    "pyproject.toml",
+    "setup.py",
+];
+
+/// Build directories of other generators, such as GN, whose `build.ninja` regenerates itself
+///
+/// Ninja writes `.ninja_log` and `.ninja_deps` wherever it builds, so one of them has to be
+/// there, and nothing that looks like sources may be
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ninja;
+impl Detector for Ninja {
+    fn name(&self) -> &'static str {
+        "ninja"
+    }
+    fn detect(&self, dir: &Path, listing: &Listing) -> Vec<Detection> {
+        // CMake and Meson builds are left to their own detectors
+        if !listing.contains("build.ninja")
+            || !(listing.contains(".ninja_log") || listing.contains(".ninja_deps"))
+            || listing.contains("CMakeCache.txt")
+            || listing.has_dir("meson-private")
+            || SOURCE_MANIFESTS.iter().any(|name| listing.contains(name))
+        {
+            return Vec::new();
+        }
+        let build_dir = project_dir(dir);
+        let is_generated = read_file(&build_dir.join("build.ninja")).is_some_and(|contents| {
+            contents.lines().any(|line| {
+                line.strip_prefix("build build.ninja")
+                    .is_some_and(|rest| rest.trim_start().starts_with(':'))
+            })
+        });
+        if !is_generated {
+            return Vec::new();
+        }
+        build_detection(build_dir, None, None)
+    }
+}
+
+/// Skips a build directory that was configured inside the sources it builds
+fn in_source_build(build_dir: &Path) -> Vec<Detection> {
+    report::skip(
+        build_dir,
+        "it is an in-source build",
+        Some(format!(
+            "Skipping build directory {}, as it is also the source directory of its project",
+            build_dir.display()
+        )),
+    );
+    Vec::new()
+}
+
+/// Returns the source directory a build directory recorded if it still exists
+///
+/// Without it there is no telling whether the build directory holds sources of its own, so it is
+/// skipped
+fn existing_source_dir(build_dir: &Path, source_dir: Option<PathBuf>) -> Option<PathBuf> {
+    let reason = match &source_dir {
+        Some(dir) if dir.is_dir() => return source_dir,
+        Some(dir) => format!("its source directory {} no longer exists", dir.display()),
+        None => "it doesn't say where its source directory is".to_owned(),
+    };
+    report::skip(
+        build_dir,
+        &reason,
+        Some(format!(
+            "Skipping build directory {}, as {}",
+            build_dir.display(),
+            reason
+        )),
+    );
+    None
+}
+
+/// Returns the detection of a build directory configured from `source_dir`
+///
+/// Build directories of generators that don't record their sources are projects of their own, so
+/// only their contents count as activity
+fn build_detection(
+    build_dir: PathBuf,
+    source_dir: Option<PathBuf>,
+    name: Option<String>,
+) -> Vec<Detection> {
+    let source_dir = source_dir.map(|dir| project_dir(&dir));
+    if source_dir
+        .as_ref()
+        .is_some_and(|dir| dir.starts_with(&build_dir))
+    {
+        return in_source_build(&build_dir);
+    }
+    let activity = source_dir.iter().cloned().collect();
+    let dir = source_dir.unwrap_or_else(|| build_dir.clone());
+    vec![Detection {
+        project: Project {
+            name: name.unwrap_or_else(|| dir_name(&dir)),
+            kind: ProjectKind::Package,
+            dir,
+            settings: None,
+        },
+        artifacts: vec![Artifact {
+            path: build_dir,
+            stray: false,
+        }],
+        activity,
+        nested: false,
+    }]
+}
+
+fn read_file(path: &Path) -> Option<String> {
+    match read_to_string(path) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            report::error(
+                path,
+                e.kind(),
+                format!("Error reading {}: {}", path.display(), e),
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{add, tree};
+    use crate::report::Event;
+
+    /// Runs `detector` on `dir`, returning the build directories it found along with their
+    /// project directories, and the reasons of what it skipped
+    fn detect(detector: impl Detector, dir: &Path) -> (Vec<(PathBuf, PathBuf)>, Vec<String>) {
+        let (detections, records) =
+            report::capture(|| detector.detect(dir, &Listing::read(dir).unwrap()));
+        let found = detections
+            .into_iter()
+            .flat_map(|detection| {
+                let project_dir = detection.project.dir;
+                detection
+                    .artifacts
+                    .into_iter()
+                    .map(move |artifact| (artifact.path, project_dir.clone()))
+            })
+            .collect();
+        let skipped = records
+            .iter()
+            .filter_map(|record| match record.event() {
+                Event::Skip(skip) => Some(skip.reason.clone()),
+                _ => None,
+            })
+            .collect();
+        (found, skipped)
+    }
+
+    fn cmake_cache(source_dir: &Path) -> String {
+        format!(
+            "CMAKE_HOME_DIRECTORY:INTERNAL={}\nCMAKE_PROJECT_NAME:STATIC=demo\n",
+            source_dir.display()
+        )
+    }
+
+    #[test]
+    fn finds_cmake_builds_of_existing_sources() {
+        let dir = tree(&[("src/CMakeLists.txt", ""), ("build/", "")]);
+        let root = dir.path().canonicalize().unwrap();
+        let build = root.join("build");
+        add(
+            &root,
+            &[("build/CMakeCache.txt", &cmake_cache(&root.join("src")))],
+        );
+        assert_eq!(
+            detect(Cmake, &build),
+            (vec![(build.clone(), root.join("src"))], Vec::new())
+        );
+        // Without its sources there is no telling what else is in there
+        add(
+            &root,
+            &[("build/CMakeCache.txt", &cmake_cache(&root.join("gone")))],
+        );
+        let (found, skipped) = detect(Cmake, &build);
+        assert!(found.is_empty());
+        assert_eq!(
+            skipped,
+            [format!(
+                "its source directory {} no longer exists",
+                root.join("gone").display()
+            )]
+        );
+        add(
+            &root,
+            &[("build/CMakeCache.txt", "CMAKE_PROJECT_NAME:STATIC=demo\n")],
+        );
+        assert_eq!(
+            detect(Cmake, &build),
+            (
+                Vec::new(),
+                vec!["it doesn't say where its source directory is".to_owned()]
+            )
+        );
+    }
+
+    #[test]
+    fn skips_cmake_builds_in_the_sources() {
+        let dir = tree(&[("src/CMakeLists.txt", ""), ("src/sub/", "")]);
+        let src = dir.path().canonicalize().unwrap().join("src");
+        add(&src, &[("CMakeCache.txt", &cmake_cache(&src))]);
+        let in_source = vec!["it is an in-source build".to_owned()];
+        assert_eq!(detect(Cmake, &src), (Vec::new(), in_source.clone()));
+        // Sources inside the build directory would be cleaned along with it
+        add(
+            &src,
+            &[("sub/CMakeCache.txt", &cmake_cache(&src.join("sub")))],
+        );
+        assert_eq!(detect(Cmake, &src.join("sub")), (Vec::new(), in_source));
+    }
+
+    #[test]
+    fn finds_configured_meson_builds() {
+        let dir = tree(&[
+            ("src/meson.build", ""),
+            ("build/meson-private/", ""),
+            ("in-source/meson.build", ""),
+            ("in-source/meson-private/", ""),
+        ]);
+        let root = dir.path().canonicalize().unwrap();
+        let build = root.join("build");
+        // Not configured yet, so the sources are unknown
+        assert_eq!(
+            detect(Meson, &build),
+            (
+                Vec::new(),
+                vec!["it doesn't say where its source directory is".to_owned()]
+            )
+        );
+        let info = format!(
+            "{{\"directories\": {{\"source\": {:?}}}}}",
+            root.join("src").display().to_string()
+        );
+        add(
+            &root,
+            &[
+                ("build/meson-info/meson-info.json", &info),
+                (
+                    "build/meson-info/intro-projectinfo.json",
+                    "{\"descriptive_name\": \"demo\"}",
+                ),
+            ],
+        );
+        assert_eq!(
+            detect(Meson, &build),
+            (vec![(build.clone(), root.join("src"))], Vec::new())
+        );
+        assert_eq!(
+            detect(Meson, &root.join("in-source")),
+            (Vec::new(), vec!["it is an in-source build".to_owned()])
+        );
+    }
+
+    #[test]
+    fn finds_only_generated_ninja_builds() {
+        let generated = "rule gn\n  command = gn gen .\nbuild build.ninja: gn\n";
+        let dir = tree(&[
+            ("out/build.ninja", generated),
+            ("out/.ninja_log", ""),
+            ("fresh/build.ninja", generated),
+            ("handwritten/build.ninja", "build app: cc main.c\n"),
+            ("handwritten/.ninja_log", ""),
+            ("sources/build.ninja", generated),
+            ("sources/.ninja_deps", ""),
+            ("sources/configure", ""),
+            ("cmake/build.ninja", generated),
+            ("cmake/.ninja_log", ""),
+            ("cmake/CMakeCache.txt", ""),
+        ]);
+        let root = dir.path().canonicalize().unwrap();
+        let out = root.join("out");
+        assert_eq!(detect(Ninja, &out), (vec![(out.clone(), out)], Vec::new()));
+        // Never built, written by hand, next to sources, or left to the CMake detector
+        for name in ["fresh", "handwritten", "sources", "cmake"] {
+            assert_eq!(detect(Ninja, &root.join(name)), (Vec::new(), Vec::new()));
+        }
+    }
+}
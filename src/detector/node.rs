@@ -0,0 +1,345 @@
+//! JavaScript projects with a `package.json`, along with the packages of npm, yarn and pnpm
+//! workspaces
+//!
+//! pnpm links the `node_modules` of workspace packages into the `.pnpm` directory of the root's.
+//! Walking a target directory never follows symlinks, so those are counted once, with the root
+//!
+//! Installed packages have a `package.json` and often a `node_modules` too, so only directories
+//! with a lockfile or a repository of their own count as projects, and nothing inside a
+//! `node_modules` ever does
+use glob::{MatchOptions, Pattern};
+use serde_json::Value;
+use std::fs::read_to_string;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use super::{project_dir, yaml_list, Artifact, Detection, Detector, Listing};
+use crate::manifest::{dir_name, Project, ProjectKind};
+use crate::report;
+
+/// Directories of installed dependencies and of the caches of bundlers and frameworks
+const ARTIFACT_DIRS: [&str; 6] = [
+    "node_modules",
+    ".next",
+    ".nuxt",
+    ".parcel-cache",
+    ".turbo",
+    ".svelte-kit",
+];
+
+/// Lockfiles of npm, yarn, pnpm and bun, which projects have but installed packages don't
+const LOCKFILES: [&str; 6] = [
+    "package-lock.json",
+    "npm-shrinkwrap.json",
+    "yarn.lock",
+    "pnpm-lock.yaml",
+    "bun.lock",
+    "bun.lockb",
+];
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Node;
+impl Detector for Node {
+    fn name(&self) -> &'static str {
+        "node"
+    }
+    fn detect(&self, dir: &Path, listing: &Listing) -> Vec<Detection> {
+        if !listing.contains("package.json")
+            || !(LOCKFILES.iter().any(|name| listing.contains(name)) || listing.contains(".git"))
+        {
+            return Vec::new();
+        }
+        let project_dir = project_dir(dir);
+        // Such as the packages of a global install, or of an editor extension
+        if project_dir.ancestors().skip(1).any(|ancestor| {
+            ancestor
+                .file_name()
+                .is_some_and(|name| name == "node_modules")
+        }) {
+            return Vec::new();
+        }
+        let manifest = read_package_json(&project_dir);
+        let members = workspace_members(&project_dir, manifest.as_ref(), listing);
+        let project = Project {
+            name: package_name(&project_dir, manifest.as_ref()),
+            kind: if members.is_some() {
+                ProjectKind::Workspace
+            } else {
+                ProjectKind::Package
+            },
+            dir: project_dir.clone(),
+            settings: None,
+        };
+        let mut detections = vec![Detection {
+            project,
+            artifacts: artifacts(&project_dir, |name| listing.has_dir(name)),
+            activity: vec![project_dir.clone()],
+            nested: false,
+        }];
+        for member_dir in members.into_iter().flatten() {
+            let artifacts = artifacts(&member_dir, |name| member_dir.join(name).is_dir());
+            if artifacts.is_empty() {
+                continue;
+            }
+            let manifest = read_package_json(&member_dir);
+            detections.push(Detection {
+                project: Project {
+                    name: package_name(&member_dir, manifest.as_ref()),
+                    kind: ProjectKind::Member(project_dir.clone()),
+                    dir: member_dir.clone(),
+                    settings: None,
+                },
+                artifacts,
+                activity: vec![member_dir],
+                nested: true,
+            });
+        }
+        detections
+    }
+}
+
+/// Returns the artifact directories in `dir` that `exists` says are there
+fn artifacts(dir: &Path, exists: impl Fn(&str) -> bool) -> Vec<Artifact> {
+    ARTIFACT_DIRS
+        .into_iter()
+        .filter(|name| exists(name))
+        .map(|name| Artifact {
+            path: dir.join(name),
+            stray: false,
+        })
+        .collect()
+}
+
+fn package_name(dir: &Path, manifest: Option<&Value>) -> String {
+    manifest
+        .and_then(|manifest| manifest.get("name")?.as_str())
+        .map(str::to_owned)
+        .unwrap_or_else(|| dir_name(dir))
+}
+
+/// Lists the package directories of the workspace rooted at `root`, `None` if it isn't one
+///
+/// npm and yarn list the packages under `workspaces` in `package.json`, either directly or in a
+/// `packages` array, while pnpm lists them in `pnpm-workspace.yaml`. Patterns starting with `!`
+/// exclude packages
+///
+/// Patterns like `packages/**` would match every installed package too, so the search never
+/// enters `node_modules` or hidden directories
+fn workspace_members(
+    root: &Path,
+    manifest: Option<&Value>,
+    listing: &Listing,
+) -> Option<Vec<PathBuf>> {
+    let patterns: Vec<String> = if listing.contains("pnpm-workspace.yaml") {
+        yaml_list(&root.join("pnpm-workspace.yaml"), "packages")
+    } else {
+        let workspaces = manifest?.get("workspaces")?;
+        let patterns = workspaces.get("packages").unwrap_or(workspaces);
+        patterns
+            .as_array()?
+            .iter()
+            .filter_map(|pattern| pattern.as_str())
+            .map(str::to_owned)
+            .collect()
+    };
+    let mut included = Vec::new();
+    let mut excluded = Vec::new();
+    for pattern in &patterns {
+        let (list, text) = match pattern.strip_prefix('!') {
+            Some(text) => (&mut excluded, text),
+            None => (&mut included, pattern.as_str()),
+        };
+        let text = text.trim_start_matches("./").trim_end_matches('/');
+        match Pattern::new(text) {
+            Ok(pattern) => list.push(pattern),
+            Err(e) => report::error(
+                root,
+                ErrorKind::InvalidInput,
+                format!(
+                    "Error in workspace package pattern {} of {}: {}",
+                    pattern,
+                    root.display(),
+                    e
+                ),
+            ),
+        }
+    }
+    // Only patterns with `**` can match at any depth
+    let max_depth = if included
+        .iter()
+        .any(|pattern| pattern.as_str().contains("**"))
+    {
+        usize::MAX
+    } else {
+        included
+            .iter()
+            .map(|pattern| Path::new(pattern.as_str()).components().count())
+            .max()
+            .unwrap_or(0)
+    };
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::new()
+    };
+    let mut members = Vec::new();
+    let walker = WalkDir::new(root)
+        .min_depth(1)
+        .max_depth(max_depth)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            entry.file_type().is_dir() && name != "node_modules" && !name.starts_with('.')
+        });
+    for entry in walker {
+        let entry = match entry {
+            Ok(v) => v,
+            Err(e) => {
+                report::error(
+                    e.path().unwrap_or(root),
+                    e.io_error().map_or(ErrorKind::Other, |e| e.kind()),
+                    format!("Error accessing entry in folder: {e}"),
+                );
+                continue;
+            }
+        };
+        let relative = entry.path().strip_prefix(root).unwrap();
+        let matches = |pattern: &Pattern| pattern.matches_path_with(relative, options);
+        if included.iter().any(matches)
+            && !excluded.iter().any(matches)
+            && entry.path().join("package.json").is_file()
+        {
+            members.push(entry.into_path());
+        }
+    }
+    Some(members)
+}
+
+fn read_package_json(dir: &Path) -> Option<Value> {
+    let manifest_path = dir.join("package.json");
+    let contents = match read_to_string(&manifest_path) {
+        Ok(v) => v,
+        Err(e) => {
+            report::error(
+                &manifest_path,
+                e.kind(),
+                format!("Error reading manifest {}: {}", manifest_path.display(), e),
+            );
+            return None;
+        }
+    };
+    match serde_json::from_str(&contents) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            report::error(
+                &manifest_path,
+                ErrorKind::InvalidData,
+                format!("Error parsing manifest {}: {}", manifest_path.display(), e),
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::tree;
+
+    fn detect(dir: &Path) -> Vec<Detection> {
+        Node.detect(dir, &Listing::read(dir).unwrap())
+    }
+
+    fn member_dirs(root: &Path, detections: &[Detection]) -> Vec<PathBuf> {
+        detections
+            .iter()
+            .filter(|detection| detection.nested)
+            .map(|detection| {
+                detection
+                    .project
+                    .dir
+                    .strip_prefix(root)
+                    .unwrap()
+                    .to_path_buf()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_workspace_members_outside_node_modules() {
+        let dir = tree(&[
+            (
+                "package.json",
+                r#"{"name": "shop", "workspaces": ["packages/**", "!packages/legacy"]}"#,
+            ),
+            ("package-lock.json", "{}"),
+            ("node_modules/left-pad/package.json", "{}"),
+            ("packages/web/package.json", r#"{"name": "web"}"#),
+            ("packages/web/node_modules/react/package.json", "{}"),
+            ("packages/web/node_modules/react/node_modules/", ""),
+            ("packages/web/.next/", ""),
+            ("packages/libs/core/package.json", "{}"),
+            ("packages/libs/core/node_modules/", ""),
+            ("packages/legacy/package.json", "{}"),
+            ("packages/legacy/node_modules/", ""),
+            ("packages/docs/README.md", ""),
+        ]);
+        let root = dir.path().canonicalize().unwrap();
+        let detections = detect(&root);
+        assert_eq!(detections[0].project.name, "shop");
+        assert_eq!(detections[0].project.kind, ProjectKind::Workspace);
+        assert_eq!(
+            member_dirs(&root, &detections),
+            [Path::new("packages/libs/core"), Path::new("packages/web")]
+        );
+        let web = &detections[2];
+        assert_eq!(web.project.name, "web");
+        assert_eq!(web.project.kind, ProjectKind::Member(root.clone()));
+        let artifacts: Vec<_> = web.artifacts.iter().map(|a| &a.path).collect();
+        assert_eq!(
+            artifacts,
+            [
+                &root.join("packages/web/node_modules"),
+                &root.join("packages/web/.next")
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_pnpm_workspace_members() {
+        let dir = tree(&[
+            ("package.json", "{}"),
+            ("pnpm-lock.yaml", ""),
+            (
+                "pnpm-workspace.yaml",
+                "packages:\n  - 'apps/*'\n  - './tools/cli/'\n",
+            ),
+            ("apps/site/package.json", "{}"),
+            ("apps/site/node_modules/", ""),
+            ("apps/site/nested/package.json", "{}"),
+            ("apps/site/nested/node_modules/", ""),
+            ("tools/cli/package.json", "{}"),
+            ("tools/cli/node_modules/", ""),
+        ]);
+        let root = dir.path().canonicalize().unwrap();
+        assert_eq!(
+            member_dirs(&root, &detect(&root)),
+            [Path::new("apps/site"), Path::new("tools/cli")]
+        );
+    }
+
+    #[test]
+    fn skips_installed_packages_and_projects_without_a_lockfile() {
+        let dir = tree(&[
+            ("package.json", "{}"),
+            ("node_modules/", ""),
+            ("lib/node_modules/tool/package.json", "{}"),
+            ("lib/node_modules/tool/package-lock.json", "{}"),
+            ("lib/node_modules/tool/node_modules/", ""),
+        ]);
+        let root = dir.path().canonicalize().unwrap();
+        assert!(detect(&root).is_empty());
+        assert!(detect(&root.join("lib/node_modules/tool")).is_empty());
+    }
+}
@@ -0,0 +1,284 @@
+//! Python projects with a `pyproject.toml`, `setup.py` or `requirements.txt`, whose virtualenvs
+//! are often the biggest thing left behind
+use std::ffi::OsStr;
+use std::fs::read_to_string;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use super::{project_dir, Artifact, Detection, Detector, Listing};
+use crate::manifest::{dir_name, Project, ProjectKind};
+use crate::report;
+
+const MANIFESTS: [&str; 3] = ["pyproject.toml", "setup.py", "requirements.txt"];
+/// Artifact directories of other build systems, whose `__pycache__` directories, if any, aren't
+/// the project's own. Hidden ones are left out too
+const OTHER_ARTIFACT_DIRS: [&str; 8] = [
+    "node_modules",
+    "target",
+    "_build",
+    "deps",
+    "dist-newstyle",
+    "zig-cache",
+    "zig-out",
+    "build",
+];
+/// Virtualenvs, which only count if they have a `pyvenv.cfg`
+const VENV_DIRS: [&str; 2] = [".venv", "venv"];
+/// Caches of test runners, type checkers and linters, along with tox and nox environments
+const CACHE_DIRS: [&str; 5] = [
+    ".pytest_cache",
+    ".mypy_cache",
+    ".ruff_cache",
+    ".tox",
+    ".nox",
+];
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Python;
+impl Detector for Python {
+    fn name(&self) -> &'static str {
+        "python"
+    }
+    fn detect(&self, dir: &Path, listing: &Listing) -> Vec<Detection> {
+        if !MANIFESTS.iter().any(|name| listing.contains(name)) {
+            // Caches are spread throughout the sources, so each is found along with the directory
+            // it is in, which keeps them to what the scan itself looks at
+            return if listing.has_dir("__pycache__") {
+                source_pycache(dir).into_iter().collect()
+            } else {
+                Vec::new()
+            };
+        }
+        let project_dir = project_dir(dir);
+        let mut artifacts: Vec<_> = VENV_DIRS
+            .into_iter()
+            .filter(|name| listing.has_dir(name) && is_venv(&project_dir.join(name)))
+            .chain(CACHE_DIRS.into_iter().filter(|name| listing.has_dir(name)))
+            .map(|name| project_dir.join(name))
+            .collect();
+        // Only packages that are built have build output, elsewhere `build` could be anything
+        let is_package = listing.contains("pyproject.toml") || listing.contains("setup.py");
+        if is_package {
+            if listing.has_dir("build") {
+                artifacts.push(project_dir.join("build"));
+            }
+            artifacts.extend(egg_info_dirs(&project_dir, listing));
+        }
+        let project = Project {
+            name: project_name(&project_dir, listing.contains("pyproject.toml")),
+            kind: ProjectKind::Package,
+            dir: project_dir.clone(),
+            settings: None,
+        };
+        let mut detections = vec![Detection {
+            project: project.clone(),
+            artifacts: to_artifacts(artifacts),
+            activity: vec![project_dir.clone()],
+            nested: false,
+        }];
+        // Like those in the rest of the sources, rather than being an artifact of the project
+        if listing.has_dir("__pycache__") {
+            detections.push(pycache_detection(project, project_dir.join("__pycache__")));
+        }
+        detections
+    }
+}
+
+/// Returns the `__pycache__` in `dir` as a cache of the package whose sources `dir` is in
+///
+/// Other directories with a `requirements.txt` are just as likely to be a home directory as a
+/// project, so only packages own the caches below them
+fn source_pycache(dir: &Path) -> Option<Detection> {
+    let dir = project_dir(dir);
+    let package_dir = owning_package(&dir)?;
+    let project = Project {
+        name: project_name(&package_dir, package_dir.join("pyproject.toml").is_file()),
+        kind: ProjectKind::Package,
+        dir: package_dir,
+        settings: None,
+    };
+    Some(pycache_detection(project, dir.join("__pycache__")))
+}
+
+fn pycache_detection(project: Project, pycache: PathBuf) -> Detection {
+    Detection {
+        activity: vec![project.dir.clone()],
+        project,
+        artifacts: to_artifacts(vec![pycache]),
+        nested: true,
+    }
+}
+
+/// Returns the package directory `dir` is in, which is the closest parent with a manifest
+///
+/// Hidden directories, virtualenvs and the artifact directories of other build systems aren't
+/// the sources of any package, and neither is anything inside a project that isn't one
+fn owning_package(dir: &Path) -> Option<PathBuf> {
+    for (below, ancestor) in dir.ancestors().zip(dir.ancestors().skip(1)) {
+        let name = below.file_name()?.to_string_lossy();
+        if name.starts_with('.') || OTHER_ARTIFACT_DIRS.contains(&&*name) || is_venv(below) {
+            return None;
+        }
+        if ["pyproject.toml", "setup.py"]
+            .iter()
+            .any(|name| ancestor.join(name).is_file())
+        {
+            return Some(ancestor.to_path_buf());
+        }
+        if ancestor.join("requirements.txt").is_file() {
+            return None;
+        }
+    }
+    None
+}
+
+fn to_artifacts(paths: Vec<PathBuf>) -> Vec<Artifact> {
+    paths
+        .into_iter()
+        .map(|path| Artifact { path, stray: false })
+        .collect()
+}
+
+fn is_venv(dir: &Path) -> bool {
+    dir.join("pyvenv.cfg").is_file()
+}
+
+/// Lists the `*.egg-info` directories of a package, which setuptools puts next to the sources,
+/// in a `src` layout too
+fn egg_info_dirs(project_dir: &Path, listing: &Listing) -> Vec<PathBuf> {
+    let is_egg_info = |name: &OsStr| name.to_string_lossy().ends_with(".egg-info");
+    let mut dirs: Vec<_> = listing
+        .dirs
+        .iter()
+        .filter(|name| is_egg_info(name))
+        .map(|name| project_dir.join(name))
+        .collect();
+    if listing.has_dir("src") {
+        let src = project_dir.join("src");
+        match src.read_dir() {
+            Ok(entries) => dirs.extend(
+                entries
+                    .flatten()
+                    .filter(|entry| is_egg_info(&entry.file_name()))
+                    .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+                    .map(|entry| entry.path()),
+            ),
+            Err(e) => report::error(
+                &src,
+                e.kind(),
+                format!("Error scanning directory {}: {}", src.display(), e),
+            ),
+        }
+    }
+    dirs
+}
+
+/// Returns the name from `pyproject.toml`, falling back to the name of the directory
+fn project_name(project_dir: &Path, has_pyproject: bool) -> String {
+    let name = has_pyproject
+        .then(|| read_pyproject(&project_dir.join("pyproject.toml")))
+        .flatten()
+        .and_then(|pyproject| {
+            let project = pyproject
+                .get("project")
+                .or_else(|| pyproject.get("tool").and_then(|tool| tool.get("poetry")))?;
+            Some(project.get("name")?.as_str()?.to_owned())
+        });
+    name.unwrap_or_else(|| dir_name(project_dir))
+}
+
+fn read_pyproject(path: &Path) -> Option<toml::Table> {
+    let contents = match read_to_string(path) {
+        Ok(v) => v,
+        Err(e) => {
+            report::error(
+                path,
+                e.kind(),
+                format!("Error reading manifest {}: {}", path.display(), e),
+            );
+            return None;
+        }
+    };
+    match contents.parse() {
+        Ok(v) => Some(v),
+        Err(e) => {
+            report::error(
+                path,
+                ErrorKind::InvalidData,
+                format!("Error parsing manifest {}: {}", path.display(), e),
+            );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{discover_targets, tree};
+    use crate::ScanOptions;
+    use glob::Pattern;
+
+    #[test]
+    fn finds_the_package_owning_sources() {
+        let dir = tree(&[
+            ("pkg/setup.py", ""),
+            ("pkg/lib/deep/mod/", ""),
+            ("pkg/.hidden/mod/", ""),
+            ("pkg/node_modules/dep/", ""),
+            ("pkg/env/pyvenv.cfg", ""),
+            ("pkg/env/lib/", ""),
+            ("pkg/scripts/requirements.txt", ""),
+            ("pkg/scripts/tool/", ""),
+            ("pkg/inner/pyproject.toml", ""),
+            ("pkg/inner/mod/", ""),
+        ]);
+        let pkg = dir.path().join("pkg");
+        assert_eq!(owning_package(&pkg.join("lib/deep/mod")), Some(pkg.clone()));
+        assert_eq!(owning_package(&pkg.join("lib")), Some(pkg.clone()));
+        assert_eq!(owning_package(&pkg.join(".hidden/mod")), None);
+        assert_eq!(owning_package(&pkg.join("node_modules/dep")), None);
+        assert_eq!(owning_package(&pkg.join("env/lib")), None);
+        assert_eq!(owning_package(&pkg.join("scripts/tool")), None);
+        assert_eq!(
+            owning_package(&pkg.join("inner/mod")),
+            Some(pkg.join("inner"))
+        );
+        assert_eq!(owning_package(dir.path()), None);
+    }
+
+    #[test]
+    fn finds_caches_only_where_the_scan_goes() {
+        let dir = tree(&[
+            ("pkg/pyproject.toml", "[project]\nname = \"pkg\"\n"),
+            ("pkg/__pycache__/", ""),
+            ("pkg/app/__pycache__/", ""),
+            ("pkg/vendored/__pycache__/", ""),
+            // Cargo projects aren't descended into without --nested
+            (
+                "pkg/ext/Cargo.toml",
+                "[package]\nname = \"ext\"\nversion = \"0.1.0\"\n",
+            ),
+            ("pkg/ext/target/", ""),
+            ("pkg/ext/scripts/__pycache__/", ""),
+        ]);
+        let root = dir.path().canonicalize().unwrap();
+        let options = ScanOptions {
+            excludes: vec![Pattern::new("vendored").unwrap()],
+            ..Default::default()
+        };
+        let (targets, _) = discover_targets(&root, &options);
+        let found: Vec<_> = targets.iter().map(|job| &job.target_path).collect();
+        assert_eq!(
+            found,
+            [
+                &root.join("pkg/__pycache__"),
+                &root.join("pkg/app/__pycache__"),
+                &root.join("pkg/ext/target"),
+            ]
+        );
+        assert!(targets[..2]
+            .iter()
+            .all(|job| job.project.dir == root.join("pkg")));
+    }
+}
@@ -0,0 +1,702 @@
+//! Finding projects and their target directories, in parallel
+//!
+//! Directories are read in parallel, with everything each one reports held back. Afterwards the
+//! results are walked in the same order a sequential scan would take, so that which path a
+//! directory is first reached through, and therefore the output, doesn't depend on timing
+//!
+//! The one exception is a directory reachable through several real paths, such as with bind
+//! mounts. It is only read through whichever path gets to it first, so that a bind mount of one
+//! of its own ancestors can't make the scan go on forever
+use glob::Pattern;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{read_dir, read_link};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::detector::{project_dir, Detection, Listing};
+use crate::manifest::{Project, ProjectKind};
+use crate::report::{self, Record};
+use crate::xdg;
+use crate::{dir_key, DirKey, ScanOptions, ScanState};
+
+/// A target directory that was found, along with the project it belongs to
+#[derive(Debug, Clone)]
+pub struct TargetJob {
+    /// Canonical path of the target directory
+    pub target_path: PathBuf,
+    pub project: Project,
+    /// Whether this is a leftover target directory of a workspace member
+    pub stray: bool,
+    /// Name of the detector that found it
+    pub kind: &'static str,
+    /// Files and directories whose modification counts as activity in the project's sources
+    pub activity: Vec<PathBuf>,
+    /// Artifact directories of the project, which don't count as activity
+    pub artifacts: Vec<PathBuf>,
+    /// Other projects that build into the same target directory, such as with a shared
+    /// `CARGO_TARGET_DIR`, whose activity counts too
+    pub shared_with: Vec<Project>,
+}
+impl TargetJob {
+    /// Adds another project found with the same target directory
+    fn share(&mut self, other: TargetJob) {
+        // Members of the project are found again with --nested, and are part of its sources.
+        // Other projects below it are not, and their protections apply as well
+        let is_member =
+            matches!(&other.project.kind, ProjectKind::Member(root) if *root == self.project.dir);
+        if is_member
+            || other.project.dir == self.project.dir
+            || self
+                .shared_with
+                .iter()
+                .any(|project| project.dir == other.project.dir)
+        {
+            return;
+        }
+        for path in other.activity {
+            if !self.activity.contains(&path) {
+                self.activity.push(path);
+            }
+        }
+        for path in other.artifacts {
+            if !self.artifacts.contains(&path) {
+                self.artifacts.push(path);
+            }
+        }
+        self.shared_with.push(other.project);
+    }
+}
+
+/// One step of a scan, in the order the steps have to be reported in
+#[derive(Debug)]
+pub enum Step {
+    /// Output that was held back while discovering
+    Records(Vec<Record>),
+    /// A target directory to check
+    Target(Box<TargetJob>),
+}
+
+/// What was found in a directory and everything below it
+struct DirNode {
+    records: Vec<Record>,
+    targets: Vec<(DirKey, TargetJob)>,
+    /// Subdirectories to scan, which are only known to be new once the tree is put in order, or
+    /// `None` if another path to the same directory was read instead
+    children: Vec<(PathBuf, DirKey, Option<DirNode>)>,
+    /// Symlinks to directories, which are scanned after everything reachable without them
+    symlinks: Vec<(PathBuf, DirKey)>,
+}
+
+/// Finds every target directory below `root`, whose key must already be in `state.visited_dirs`
+///
+/// Target directories reached through real paths win over those reached through symlinks, and
+/// each physical directory is only scanned once, which also takes care of symlink cycles
+pub fn discover(root: PathBuf, options: &ScanOptions, state: &mut ScanState) -> Vec<Step> {
+    let mut steps = Vec::new();
+    let mut symlinks = VecDeque::new();
+    let mut found = HashMap::new();
+    let claimed = Mutex::new(state.visited_dirs.clone());
+    let node = scan_dir(root, Vec::new(), options, &claimed);
+    order(node, state, &mut steps, &mut found, &mut symlinks);
+    while let Some((link, key)) = symlinks.pop_front() {
+        if !state.visited_dirs.insert(key) {
+            steps.push(Step::Records(already_scanned(&link)));
+            continue;
+        }
+        claimed.lock().unwrap().insert(key);
+        let node = scan_dir(link, Vec::new(), options, &claimed);
+        order(node, state, &mut steps, &mut found, &mut symlinks);
+    }
+    steps
+}
+
+/// Puts what was found below a directory in order, skipping anything seen before
+///
+/// `found` has the index in `steps` of each target directory found so far
+fn order(
+    node: DirNode,
+    state: &mut ScanState,
+    steps: &mut Vec<Step>,
+    found: &mut HashMap<DirKey, usize>,
+    symlinks: &mut VecDeque<(PathBuf, DirKey)>,
+) {
+    steps.push(Step::Records(node.records));
+    for (key, job) in node.targets {
+        // Several projects can share a target directory, which is reported with the first one
+        // found but is only as old as the most recent of them. Those found under earlier roots
+        // were already dealt with
+        if state.cleaned_targets.insert(key) {
+            state.visited_dirs.insert(key);
+            found.insert(key, steps.len());
+            steps.push(Step::Target(Box::new(job)));
+        } else if let Some(Step::Target(first)) = found.get(&key).map(|&index| &mut steps[index]) {
+            first.share(job);
+        }
+    }
+    for (path, key, child) in node.children {
+        match child {
+            Some(child) if state.visited_dirs.insert(key) => {
+                order(child, state, steps, found, symlinks)
+            }
+            // The path that was read is only marked visited once it is reached
+            _ => steps.push(Step::Records(already_scanned(&path))),
+        }
+    }
+    symlinks.extend(node.symlinks);
+}
+
+/// Reads a directory and everything below it, with subdirectories being read in parallel
+///
+/// `artifacts` are the paths relative to `dir` of artifact directories below it that projects
+/// further up found, which aren't scanned. `claimed` has the keys of the directories that were
+/// read already or are being read, a subdirectory is only read if its key can be added to it
+fn scan_dir(
+    dir: PathBuf,
+    artifacts: Vec<PathBuf>,
+    options: &ScanOptions,
+    claimed: &Mutex<HashSet<DirKey>>,
+) -> DirNode {
+    let (contents, records) = report::capture(|| read_project_dir(&dir, &artifacts, options));
+    let children = contents
+        .subdirs
+        .into_par_iter()
+        .map(|(path, key)| {
+            let name = path.file_name().unwrap_or_default();
+            let artifacts = contents
+                .artifacts
+                .iter()
+                .filter_map(|artifact| artifact.strip_prefix(name).ok())
+                .map(Path::to_path_buf)
+                .collect();
+            // Claimed before reading, as the lock must not be held while reading
+            let is_new = claimed.lock().unwrap().insert(key);
+            let child = is_new.then(|| scan_dir(path.clone(), artifacts, options, claimed));
+            (path, key, child)
+        })
+        .collect();
+    DirNode {
+        records,
+        targets: contents.targets,
+        children,
+        symlinks: contents.symlinks,
+    }
+}
+
+/// What reading a single directory found
+#[derive(Default)]
+struct DirContents {
+    targets: Vec<(DirKey, TargetJob)>,
+    subdirs: Vec<(PathBuf, DirKey)>,
+    symlinks: Vec<(PathBuf, DirKey)>,
+    /// Artifact directories further below, relative to the directory
+    artifacts: Vec<PathBuf>,
+}
+
+/// Finds the target directories of the projects in `dir` if there are any, along with the
+/// subdirectories and symlinks to directories in it that aren't artifact directories
+fn read_project_dir(dir: &Path, artifacts: &[PathBuf], options: &ScanOptions) -> DirContents {
+    let mut subdirs = Vec::new();
+    let mut symlinks = Vec::new();
+    let mut listing = Listing::default();
+    match read_dir(dir) {
+        Ok(entries) => {
+            let mut entries: Vec<_> = entries
+                .filter_map(|entry| match entry {
+                    Ok(entry) => Some(entry),
+                    Err(e) => {
+                        report::error(
+                            dir,
+                            e.kind(),
+                            format!("Error accessing entry in folder: {e}"),
+                        );
+                        None
+                    }
+                })
+                .collect();
+            // The order of read_dir is up to the filesystem
+            entries.sort_by_key(|entry| entry.file_name());
+            for entry in entries {
+                let path = entry.path();
+                let file_type = match entry.file_type() {
+                    Ok(v) => v,
+                    Err(e) => {
+                        report::error(
+                            &path,
+                            e.kind(),
+                            format!("Error reading file type of {}: {}", path.display(), e),
+                        );
+                        continue;
+                    }
+                };
+                listing.names.insert(entry.file_name());
+                if file_type.is_dir() {
+                    listing.dirs.insert(entry.file_name());
+                    subdirs.push(path);
+                } else if file_type.is_symlink() {
+                    match read_link(&path) {
+                        Ok(inner) => {
+                            let symlink_target = if inner.is_relative() {
+                                dir.join(&inner)
+                            } else {
+                                inner
+                            };
+                            match std::fs::metadata(&symlink_target) {
+                                Ok(metadata) => {
+                                    if metadata.is_dir() {
+                                        listing.dirs.insert(entry.file_name());
+                                        symlinks.push(path);
+                                    }
+                                }
+                                Err(e) => report::error(
+                                    &path,
+                                    e.kind(),
+                                    format!(
+                                        "Error reading metadata of entry {} behind symlink {}: {}",
+                                        symlink_target.display(),
+                                        path.display(),
+                                        e
+                                    ),
+                                ),
+                            }
+                        }
+                        Err(e) => report::error(
+                            &path,
+                            e.kind(),
+                            format!("Error following symlink {}: {}", path.display(), e),
+                        ),
+                    }
+                }
+            }
+        }
+        Err(e) => report::error(
+            dir,
+            e.kind(),
+            format!("Error scanning directory {}: {}", dir.display(), e),
+        ),
+    }
+    let mut targets = Vec::new();
+    let mut has_target = false;
+    let mut target_keys = Vec::new();
+    let mut artifacts = artifacts.to_vec();
+    let real_dir = project_dir(dir);
+    let detections: Vec<_> = options
+        .detectors
+        .iter()
+        .map(|detector| (detector, detector.detect(dir, &listing)))
+        .collect();
+    // Activity walks skip the artifacts of every project found here by any detector, as workspace
+    // roots contain those of their members, and projects of one build system those of another
+    let skipped: Vec<_> = detections
+        .iter()
+        .flat_map(|(_, detections)| detections)
+        .flat_map(|detection| &detection.artifacts)
+        .map(|artifact| artifact.path.clone())
+        .collect();
+    for (detector, detections) in detections {
+        for detection in detections {
+            // Artifact directories never contain projects worth cleaning, wherever they are
+            artifacts.extend(
+                detection
+                    .artifacts
+                    .iter()
+                    .filter_map(|artifact| artifact.path.strip_prefix(&real_dir).ok())
+                    .map(Path::to_path_buf),
+            );
+            if !detection.nested && !detection.artifacts.is_empty() {
+                has_target |= detector.stops_descent();
+                target_keys.extend(
+                    detection
+                        .artifacts
+                        .iter()
+                        .filter_map(|artifact| dir_key(&artifact.path).ok()),
+                );
+            }
+            targets.extend(target_jobs(detection, detector.name(), &skipped));
+        }
+    }
+    // A directory that is build output itself is never descended into, even for nested projects
+    let is_target = dir_key(dir).is_ok_and(|key| target_keys.contains(&key));
+    if is_target || (has_target && !options.nested) {
+        return DirContents {
+            targets,
+            ..Default::default()
+        };
+    }
+    let is_artifact = |path: &Path| {
+        path.file_name()
+            .is_some_and(|name| artifacts.iter().any(|artifact| artifact == Path::new(name)))
+    };
+    subdirs.retain(|path| !is_artifact(path));
+    symlinks.retain(|path| !is_artifact(path));
+    // Keys are read here so that they are ready once the tree is put in order
+    let keyed = |paths: Vec<PathBuf>| {
+        paths
+            .into_iter()
+            .filter(|path| match excluded_by(path, &options.excludes) {
+                Some(pattern) => {
+                    report::skip(
+                        path,
+                        format!("excluded by {}", pattern.as_str()),
+                        Some(format!(
+                            "Skipping {}, as it is excluded by {}",
+                            path.display(),
+                            pattern.as_str()
+                        )),
+                    );
+                    false
+                }
+                None => true,
+            })
+            .filter_map(|path| Some((read_key(&path)?, path)))
+            .filter(|(key, _)| !target_keys.contains(key))
+            .filter(|(key, path)| {
+                let reserved = is_reserved(path, key, reserved_dirs());
+                if reserved {
+                    report::skip(path, "it holds cleaned up directories", None);
+                }
+                !reserved
+            })
+            .map(|(key, path)| (path, key))
+            .collect()
+    };
+    DirContents {
+        targets,
+        subdirs: keyed(subdirs),
+        symlinks: keyed(symlinks),
+        artifacts,
+    }
+}
+
+/// Returns the state directory, which has the quarantine in it, and the home trash
+fn reserved_dirs() -> &'static [PathBuf] {
+    static RESERVED_DIRS: OnceLock<Vec<PathBuf>> = OnceLock::new();
+    RESERVED_DIRS.get_or_init(|| {
+        [
+            xdg::state_dir().ok(),
+            xdg::data_home().map(|dir| dir.join("Trash")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    })
+}
+
+/// Returns whether `path` is where this tool or the trash keeps what was cleaned up, which would
+/// be found again otherwise
+///
+/// That is any of `reserved_dirs`, along with the trash and quarantine directories at the top of
+/// other filesystems. Their keys are read every time, as they may only be created by cleaning up
+/// during the scan
+fn is_reserved(path: &Path, key: &DirKey, reserved_dirs: &[PathBuf]) -> bool {
+    if reserved_dirs
+        .iter()
+        .any(|dir| dir_key(dir).is_ok_and(|reserved| reserved == *key))
+    {
+        return true;
+    }
+    #[cfg(unix)]
+    {
+        let uid = unsafe { libc::getuid() };
+        let name = path.file_name().unwrap_or_default();
+        name == ".Trash"
+            || *name == *format!(".Trash-{uid}")
+            || *name == *format!(".{}-quarantine-{uid}", xdg::APP_NAME)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        false
+    }
+}
+
+/// Returns the exclude glob matching `path`, where globs without a slash match directory names
+fn excluded_by<'a>(path: &Path, excludes: &'a [Pattern]) -> Option<&'a Pattern> {
+    excludes.iter().find(|pattern| {
+        if pattern.as_str().contains('/') {
+            // Roots may be given as relative paths, while globs are usually written as absolute ones
+            pattern.matches_path(path)
+                || std::path::absolute(path).is_ok_and(|path| pattern.matches_path(&path))
+        } else {
+            path.file_name()
+                .is_some_and(|name| pattern.matches(&name.to_string_lossy()))
+        }
+    })
+}
+
+/// Resolves the artifact directories of a project to where they really are
+fn target_jobs(
+    detection: Detection,
+    kind: &'static str,
+    skipped: &[PathBuf],
+) -> Vec<(DirKey, TargetJob)> {
+    detection
+        .artifacts
+        .into_iter()
+        .filter_map(|artifact| {
+            // Resolve symlinks so that we report and delete the real location of the target directory
+            let target_path = match artifact.path.canonicalize() {
+                Ok(v) => v,
+                Err(e) => {
+                    report::error(
+                        &artifact.path,
+                        e.kind(),
+                        format!("Error resolving path {}: {}", artifact.path.display(), e),
+                    );
+                    return None;
+                }
+            };
+            let key = read_key(&target_path)?;
+            let mut artifacts = skipped.to_vec();
+            artifacts.push(target_path.clone());
+            Some((
+                key,
+                TargetJob {
+                    target_path,
+                    project: detection.project.clone(),
+                    stray: artifact.stray,
+                    kind,
+                    activity: detection.activity.clone(),
+                    artifacts,
+                    shared_with: Vec::new(),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Returns the key of the directory at `path`, reporting it if that fails
+fn read_key(path: &Path) -> Option<DirKey> {
+    match dir_key(path) {
+        Ok(key) => Some(key),
+        Err(e) => {
+            report::error(
+                path,
+                e.kind(),
+                format!("Error reading metadata of {}: {}", path.display(), e),
+            );
+            None
+        }
+    }
+}
+
+fn already_scanned(path: &Path) -> Vec<Record> {
+    let ((), records) = report::capture(|| {
+        report::skip(
+            path,
+            "already scanned through another path",
+            Some(format!(
+                "Skipping {}, as it has already been scanned through another path",
+                path.display()
+            )),
+        )
+    });
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{discover_targets, tree};
+
+    fn package(name: &str) -> String {
+        format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\n")
+    }
+
+    #[test]
+    fn shares_target_directories_with_nested_projects() {
+        let dir = tree(&[
+            (".cargo/config.toml", "[build]\ntarget-dir = \"shared\"\n"),
+            ("shared/", ""),
+            ("a/Cargo.toml", &package("a")),
+            ("a/b/Cargo.toml", &package("b")),
+            ("c/Cargo.toml", &package("c")),
+        ]);
+        let root = dir.path().canonicalize().unwrap();
+        let options = ScanOptions {
+            nested: true,
+            ..Default::default()
+        };
+        let (targets, _) = discover_targets(&root, &options);
+        assert_eq!(targets.len(), 1);
+        let job = &targets[0];
+        assert_eq!(job.target_path, root.join("shared"));
+        assert_eq!(job.project.dir, root.join("a"));
+        // The project below a isn't one of its members, so it can protect the target directory
+        let shared: Vec<_> = job.shared_with.iter().map(|project| &project.dir).collect();
+        assert_eq!(shared, [&root.join("a/b"), &root.join("c")]);
+        assert!(job.activity.contains(&root.join("a/b")));
+        assert!(job.activity.contains(&root.join("c")));
+    }
+
+    #[test]
+    fn leaves_workspace_members_out_of_sharing() {
+        let dir = tree(&[("w/Cargo.toml", ""), ("w/m/Cargo.toml", "")]);
+        let root = dir.path().canonicalize().unwrap();
+        let project = |dir: &str, kind| Project {
+            dir: root.join(dir),
+            name: dir.to_owned(),
+            kind,
+            settings: None,
+        };
+        let job = |project| TargetJob {
+            target_path: root.join("target"),
+            project,
+            stray: false,
+            kind: "cargo",
+            activity: Vec::new(),
+            artifacts: Vec::new(),
+            shared_with: Vec::new(),
+        };
+        let mut first = job(project("w", ProjectKind::Workspace));
+        first.share(job(project("w/m", ProjectKind::Member(root.join("w")))));
+        first.share(job(project("w", ProjectKind::Workspace)));
+        assert!(first.shared_with.is_empty());
+        // Members of other workspaces only share
+        first.share(job(project("w/m", ProjectKind::Member(root.join("v")))));
+        assert_eq!(first.shared_with.len(), 1);
+    }
+
+    #[test]
+    fn reserves_state_directories_created_during_the_scan() {
+        let dir = tree(&[("project/", "")]);
+        let state = dir.path().join("state");
+        let reserved =
+            |path: &Path| is_reserved(path, &dir_key(path).unwrap(), std::slice::from_ref(&state));
+        assert!(!reserved(&dir.path().join("project")));
+        std::fs::create_dir(&state).unwrap();
+        assert!(reserved(&state));
+        // Through any path that leads to it
+        #[cfg(unix)]
+        {
+            let link = dir.path().join("link");
+            std::os::unix::fs::symlink(&state, &link).unwrap();
+            assert!(reserved(&link));
+        }
+    }
+
+    /// Returns the paths that were skipped for having been scanned through another path
+    fn scanned_elsewhere(records: &[Record]) -> Vec<&Path> {
+        records
+            .iter()
+            .filter_map(|record| match record.event() {
+                report::Event::Skip(skip)
+                    if skip.reason == "already scanned through another path" =>
+                {
+                    Some(skip.path.as_path())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ends_symlink_loops() {
+        use std::os::unix::fs::symlink;
+        let dir = tree(&[
+            ("a/Cargo.toml", &package("a")),
+            ("a/target/", ""),
+            ("dir/", ""),
+        ]);
+        let root = dir.path().canonicalize().unwrap();
+        symlink(&root, root.join("dir/root")).unwrap();
+        symlink(".", root.join("dir/itself")).unwrap();
+        let (targets, records) = discover_targets(&root, &ScanOptions::default());
+        let found: Vec<_> = targets.iter().map(|job| &job.target_path).collect();
+        assert_eq!(found, [&root.join("a/target")]);
+        assert_eq!(
+            scanned_elsewhere(&records),
+            [root.join("dir/itself"), root.join("dir/root")]
+        );
+        assert!(!records.iter().any(Record::is_error));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn scans_directories_reachable_through_several_paths_once() {
+        use std::os::unix::fs::symlink;
+        let dir = tree(&[
+            ("scanned/real/p/Cargo.toml", &package("p")),
+            ("scanned/real/p/target/", ""),
+            ("outside/q/Cargo.toml", &package("q")),
+            ("outside/q/target/", ""),
+        ]);
+        let root = dir.path().canonicalize().unwrap();
+        let scanned = root.join("scanned");
+        // Real paths win over symlinks that come first, and the first of several symlinks wins
+        symlink(scanned.join("real"), scanned.join("a link")).unwrap();
+        symlink(root.join("outside"), scanned.join("b link")).unwrap();
+        symlink(root.join("outside"), scanned.join("c link")).unwrap();
+        let (targets, records) = discover_targets(&scanned, &ScanOptions::default());
+        let found: Vec<_> = targets.iter().map(|job| &job.target_path).collect();
+        assert_eq!(
+            found,
+            [
+                &scanned.join("real/p/target"),
+                &root.join("outside/q/target")
+            ]
+        );
+        assert_eq!(
+            scanned_elsewhere(&records),
+            [scanned.join("a link"), scanned.join("c link")]
+        );
+    }
+
+    #[test]
+    fn skips_excluded_directories() {
+        let dir = tree(&[
+            ("keep-a/Cargo.toml", &package("a")),
+            ("keep-a/target/", ""),
+            ("b/Cargo.toml", &package("b")),
+            ("b/target/", ""),
+            ("vendor/c/Cargo.toml", &package("c")),
+            ("vendor/c/target/", ""),
+            ("other/vendor/d/Cargo.toml", &package("d")),
+            ("other/vendor/d/target/", ""),
+        ]);
+        let root = dir.path().canonicalize().unwrap();
+        let options = ScanOptions {
+            excludes: vec![
+                Pattern::new("keep-*").unwrap(),
+                Pattern::new(&format!("{}/vendor/*", root.display())).unwrap(),
+            ],
+            ..Default::default()
+        };
+        let (targets, records) = discover_targets(&root, &options);
+        let found: Vec<_> = targets.iter().map(|job| &job.target_path).collect();
+        // Globs with a slash match whole paths, so other directories named vendor are scanned
+        assert_eq!(
+            found,
+            [&root.join("b/target"), &root.join("other/vendor/d/target")]
+        );
+        let skipped: Vec<_> = records
+            .iter()
+            .filter_map(|record| match record.event() {
+                report::Event::Skip(skip) => Some((skip.path.clone(), skip.reason.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            skipped,
+            [
+                (root.join("keep-a"), "excluded by keep-*".to_owned()),
+                (
+                    root.join("vendor/c"),
+                    format!("excluded by {}/vendor/*", root.display())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_path_globs_against_relative_paths() {
+        let cwd = std::env::current_dir().unwrap();
+        let excludes = [Pattern::new(&format!("{}/src/*", cwd.display())).unwrap()];
+        assert!(excluded_by(Path::new("src/detector"), &excludes).is_some());
+        assert!(excluded_by(Path::new("src"), &excludes).is_none());
+        let names = [Pattern::new("de*").unwrap()];
+        assert!(excluded_by(Path::new("src/detector"), &names).is_some());
+        assert!(excluded_by(Path::new("detector/src"), &names).is_none());
+    }
+}
@@ -0,0 +1,124 @@
+//! Errors that are returned to code embedding the cleaner instead of being printed
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum Error {
+    /// The root of a scan couldn't be read
+    Root {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// Something below the root couldn't be read, so it was skipped or its target directory kept
+    Scan {
+        path: PathBuf,
+        kind: ErrorKind,
+        message: String,
+    },
+    Delete {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Trash {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Quarantine {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Archive {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// A target directory couldn't be written to a report
+    Report {
+        path: PathBuf,
+        source: io::Error,
+    },
+}
+impl Error {
+    /// Returns the path the error is about
+    pub fn path(&self) -> &Path {
+        match self {
+            Error::Root { path, .. }
+            | Error::Scan { path, .. }
+            | Error::Delete { path, .. }
+            | Error::Trash { path, .. }
+            | Error::Quarantine { path, .. }
+            | Error::Archive { path, .. }
+            | Error::Report { path, .. } => path,
+        }
+    }
+    /// Returns the kind of the IO error behind this
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Scan { kind, .. } => *kind,
+            Error::Root { source, .. }
+            | Error::Delete { source, .. }
+            | Error::Trash { source, .. }
+            | Error::Quarantine { source, .. }
+            | Error::Archive { source, .. }
+            | Error::Report { source, .. } => source.kind(),
+        }
+    }
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Root { path, source } => {
+                write!(
+                    f,
+                    "Error reading metadata of {}: {}",
+                    path.display(),
+                    source
+                )
+            }
+            Error::Scan { message, .. } => f.write_str(message),
+            Error::Delete { path, source } => write!(
+                f,
+                "Error deleting target directory {}: {}",
+                path.display(),
+                source
+            ),
+            Error::Trash { path, source } => write!(
+                f,
+                "Error moving target directory {} to the trash, leaving it in place: {}",
+                path.display(),
+                source
+            ),
+            Error::Quarantine { path, source } => write!(
+                f,
+                "Error quarantining target directory {}, leaving it in place: {}",
+                path.display(),
+                source
+            ),
+            Error::Archive { path, source } => write!(
+                f,
+                "Error archiving target directory {}, leaving it in place: {}",
+                path.display(),
+                source
+            ),
+            Error::Report { path, source } => write!(
+                f,
+                "Error reporting target directory {}: {}",
+                path.display(),
+                source
+            ),
+        }
+    }
+}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Scan { .. } => None,
+            Error::Root { source, .. }
+            | Error::Delete { source, .. }
+            | Error::Trash { source, .. }
+            | Error::Quarantine { source, .. }
+            | Error::Archive { source, .. }
+            | Error::Report { source, .. } => Some(source),
+        }
+    }
+}
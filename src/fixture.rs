@@ -0,0 +1,56 @@
+//! Directory trees for tests
+use filetime::FileTime;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+use tempfile::TempDir;
+use walkdir::WalkDir;
+
+use crate::discovery::{discover, Step, TargetJob};
+use crate::report::{self, Record};
+use crate::{dir_key, ScanOptions, ScanState};
+
+/// Creates a temporary directory holding `files`, which are paths relative to it along with their
+/// contents. Paths ending in `/` are created as empty directories
+pub fn tree(files: &[(&str, &str)]) -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    add(dir.path(), files);
+    dir
+}
+
+/// Adds `files` to the directory at `root`, as [`tree`] does
+pub fn add(root: &Path, files: &[(&str, &str)]) {
+    for (path, contents) in files {
+        let path = root.join(path);
+        if path.to_string_lossy().ends_with('/') {
+            fs::create_dir_all(&path).unwrap();
+        } else {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, contents).unwrap();
+        }
+    }
+}
+
+/// Sets the modification time of everything at and below `path` to `time`
+pub fn set_modified(path: &Path, time: SystemTime) {
+    let time = FileTime::from_system_time(time);
+    for entry in WalkDir::new(path) {
+        filetime::set_symlink_file_times(entry.unwrap().path(), time, time).unwrap();
+    }
+}
+
+/// Finds the target directories below `root` in order, along with what was reported
+pub fn discover_targets(root: &Path, options: &ScanOptions) -> (Vec<TargetJob>, Vec<Record>) {
+    let mut state = ScanState::default();
+    state.visited_dirs.insert(dir_key(root).unwrap());
+    let (steps, mut records) =
+        report::capture(|| discover(root.to_path_buf(), options, &mut state));
+    let mut targets = Vec::new();
+    for step in steps {
+        match step {
+            Step::Records(step_records) => records.extend(step_records),
+            Step::Target(job) => targets.push(*job),
+        }
+    }
+    (targets, records)
+}
@@ -0,0 +1,185 @@
+//! Cleaning just enough of the least recently used target directories to reach a free space goal
+use humansize::DECIMAL;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// How much space a cleanup should free
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Goal {
+    /// Free this many bytes in total
+    Free(u64),
+    /// Free space until every filesystem holding a target directory has this much available
+    UntilFree(FreeSpace),
+}
+impl fmt::Display for Goal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Goal::Free(bytes) => write!(f, "freeing {}", humansize::format_size(*bytes, DECIMAL)),
+            Goal::UntilFree(free) => write!(f, "having {free} free"),
+        }
+    }
+}
+
+/// An amount of available space on a filesystem
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FreeSpace {
+    Bytes(u64),
+    /// Percentage of the size of the filesystem
+    Percent(f64),
+}
+impl FreeSpace {
+    /// Returns the number of bytes this is on a filesystem of `total` bytes
+    pub fn bytes_of(self, total: u64) -> u64 {
+        match self {
+            FreeSpace::Bytes(bytes) => bytes,
+            FreeSpace::Percent(percent) => (total as f64 * percent / 100.0) as u64,
+        }
+    }
+}
+impl fmt::Display for FreeSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FreeSpace::Bytes(bytes) => f.write_str(&humansize::format_size(*bytes, DECIMAL)),
+            FreeSpace::Percent(percent) => write!(f, "{percent}%"),
+        }
+    }
+}
+
+/// Parses a size such as `500M`, `20GB` or `1.5GiB`, a plain number is in bytes
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("{s:?} doesn't start with a number"))?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1000,
+        "m" | "mb" => 1000_u64.pow(2),
+        "g" | "gb" => 1000_u64.pow(3),
+        "t" | "tb" => 1000_u64.pow(4),
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        unit => return Err(format!("unknown size unit {unit:?}")),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Parses a size like [`parse_size`], or a percentage of the filesystem such as `20%`
+pub fn parse_free_space(s: &str) -> Result<FreeSpace, String> {
+    match s.trim().strip_suffix('%') {
+        Some(percent) => {
+            let percent: f64 = percent
+                .trim()
+                .parse()
+                .map_err(|_| format!("{s:?} isn't a percentage"))?;
+            if !(0.0..=100.0).contains(&percent) {
+                return Err(format!("{s:?} isn't between 0% and 100%"));
+            }
+            Ok(FreeSpace::Percent(percent))
+        }
+        None => parse_size(s).map(FreeSpace::Bytes),
+    }
+}
+
+/// Space on the filesystem holding some path
+#[derive(Debug, Clone, Copy)]
+pub struct FilesystemSpace {
+    /// Bytes available to unprivileged users, as shown by `df`
+    pub available: u64,
+    pub total: u64,
+}
+
+/// Returns the space on the filesystem holding `path`
+#[cfg(unix)]
+pub fn filesystem_space(path: &Path) -> io::Result<FilesystemSpace> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // The field types differ between platforms
+    #[allow(clippy::unnecessary_cast)]
+    let block_size = stat.f_frsize as u64;
+    #[allow(clippy::unnecessary_cast)]
+    Ok(FilesystemSpace {
+        available: stat.f_bavail as u64 * block_size,
+        total: stat.f_blocks as u64 * block_size,
+    })
+}
+#[cfg(not(unix))]
+pub fn filesystem_space(_path: &Path) -> io::Result<FilesystemSpace> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "checking free space is only supported on unix",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("12b"), Ok(12));
+        assert_eq!(parse_size("500M"), Ok(500_000_000));
+        assert_eq!(parse_size("20GB"), Ok(20_000_000_000));
+        assert_eq!(parse_size(" 2 kb "), Ok(2000));
+        assert_eq!(parse_size("1T"), Ok(1_000_000_000_000));
+        assert_eq!(parse_size("1.5GiB"), Ok(1_610_612_736));
+        assert_eq!(parse_size("4KiB"), Ok(4096));
+        assert_eq!(parse_size("0.5mib"), Ok(524_288));
+    }
+
+    #[test]
+    fn rejects_bad_sizes() {
+        assert_eq!(
+            parse_size("GB"),
+            Err("\"GB\" doesn't start with a number".to_owned())
+        );
+        assert_eq!(
+            parse_size("1.2.3G"),
+            Err("\"1.2.3G\" doesn't start with a number".to_owned())
+        );
+        assert_eq!(
+            parse_size("5 parsecs"),
+            Err("unknown size unit \"parsecs\"".to_owned())
+        );
+        assert!(parse_size("").is_err());
+        assert!(parse_size("-5G").is_err());
+    }
+
+    #[test]
+    fn parses_free_space() {
+        assert_eq!(parse_free_space("20%"), Ok(FreeSpace::Percent(20.0)));
+        assert_eq!(parse_free_space(" 12.5 % "), Ok(FreeSpace::Percent(12.5)));
+        assert_eq!(
+            parse_free_space("10G"),
+            Ok(FreeSpace::Bytes(10_000_000_000))
+        );
+        assert_eq!(
+            parse_free_space("150%"),
+            Err("\"150%\" isn't between 0% and 100%".to_owned())
+        );
+        assert_eq!(
+            parse_free_space("lots%"),
+            Err("\"lots%\" isn't a percentage".to_owned())
+        );
+    }
+
+    #[test]
+    fn measures_free_space_on_a_filesystem() {
+        assert_eq!(FreeSpace::Bytes(5).bytes_of(1000), 5);
+        assert_eq!(FreeSpace::Percent(12.5).bytes_of(1000), 125);
+        assert_eq!(FreeSpace::Percent(100.0).bytes_of(1000), 1000);
+    }
+}
@@ -0,0 +1,162 @@
+//! Asking before each target directory is cleaned, and remembering projects that should never be
+//! offered again
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, BufRead, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use crate::serde_path;
+use crate::xdg::state_dir;
+
+/// What was answered to a prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Answer {
+    Yes,
+    No,
+    /// Yes to this and every following target directory
+    All,
+    /// No to this and every following target directory
+    SkipRest,
+    /// No, and never offer this project again
+    Pin,
+}
+
+/// Whether there is still anything to ask
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Prompting {
+    #[default]
+    Ask,
+    YesToAll,
+    NoToAll,
+}
+
+/// Asks `question` on the terminal until it gets a valid answer
+///
+/// Prompts go to stderr so that they don't end up in JSON output. Running out of input counts as
+/// skipping the rest
+pub fn ask(question: &str) -> Answer {
+    let stdin = io::stdin();
+    loop {
+        eprint!("{question} [y]es/[n]o/[a]ll/[s]kip rest/[p]in forever: ");
+        let _ = io::stderr().flush();
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => {
+                eprintln!();
+                return Answer::SkipRest;
+            }
+            Ok(_) => (),
+        }
+        match line.trim().to_ascii_lowercase().as_str() {
+            "y" | "yes" => return Answer::Yes,
+            "n" | "no" => return Answer::No,
+            "a" | "all" => return Answer::All,
+            "s" | "skip" | "skip rest" => return Answer::SkipRest,
+            "p" | "pin" | "pin forever" => return Answer::Pin,
+            _ => eprintln!("Please answer y, n, a, s or p"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Pins {
+    projects: Vec<Pin>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Pin {
+    #[serde(with = "serde_path")]
+    project_dir: PathBuf,
+}
+
+/// Returns the project directories that were pinned, which are never cleaned
+pub fn load_pins() -> io::Result<HashSet<PathBuf>> {
+    load_pins_from(&pins_path()?)
+}
+
+fn load_pins_from(path: &Path) -> io::Result<HashSet<PathBuf>> {
+    match fs::read(path) {
+        Ok(contents) => {
+            let pins: Pins = serde_json::from_slice(&contents)?;
+            Ok(pins
+                .projects
+                .into_iter()
+                .map(|pin| pin.project_dir)
+                .collect())
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(HashSet::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Pins the project in `project_dir` so that its target directory is never cleaned
+pub fn pin(project_dir: &Path) -> io::Result<PathBuf> {
+    let path = pins_path()?;
+    pin_in(&path, project_dir)?;
+    Ok(path)
+}
+
+/// Pins the project in `project_dir` in the pins file at `path`
+fn pin_in(path: &Path, project_dir: &Path) -> io::Result<()> {
+    let mut pins: Pins = match fs::read(path) {
+        Ok(contents) => serde_json::from_slice(&contents)?,
+        Err(e) if e.kind() == ErrorKind::NotFound => Pins::default(),
+        Err(e) => return Err(e),
+    };
+    if !pins
+        .projects
+        .iter()
+        .any(|pin| pin.project_dir == project_dir)
+    {
+        pins.projects.push(Pin {
+            project_dir: project_dir.to_path_buf(),
+        });
+    }
+    fs::create_dir_all(path.parent().unwrap())?;
+    // Write to a temporary file first so that a crash never leaves a truncated file behind
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_vec_pretty(&pins)?)?;
+    fs::rename(&temp_path, path)
+}
+
+pub fn pins_path() -> io::Result<PathBuf> {
+    Ok(state_dir()?.join("pins.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::tree;
+
+    #[test]
+    fn remembers_pinned_projects() {
+        let dir = tree(&[]);
+        let path = dir.path().join("state/pins.json");
+        assert!(load_pins_from(&path).unwrap().is_empty());
+        let (a, b) = (Path::new("/code/a"), Path::new("/code/b"));
+        pin_in(&path, a).unwrap();
+        pin_in(&path, b).unwrap();
+        // Pinning again changes nothing
+        pin_in(&path, a).unwrap();
+        let pins: Pins = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(pins.projects.len(), 2);
+        assert_eq!(
+            load_pins_from(&path).unwrap(),
+            HashSet::from([a.to_path_buf(), b.to_path_buf()])
+        );
+        assert!(!path.with_extension("json.tmp").exists());
+    }
+
+    #[test]
+    fn refuses_to_overwrite_unreadable_pins() {
+        let dir = tree(&[("pins.json", "not json")]);
+        let path = dir.path().join("pins.json");
+        assert_eq!(
+            load_pins_from(&path).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        assert!(pin_in(&path, Path::new("/code/a")).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "not json");
+    }
+}
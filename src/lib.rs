@@ -0,0 +1,1566 @@
+//! Finds the build output of projects that haven't been worked on in a while and cleans it up,
+//! such as the target directories of Rust projects, the `node_modules` of JavaScript ones, the
+//! virtualenvs of Python ones, the build directories of Gradle, Maven and sbt, and CMake and
+//! Meson build directories wherever they were configured
+//!
+//! A [`Scanner`] finds and measures the target directories below a root, a [`Policy`] decides
+//! which of them to clean, and an [`Action`] does the cleaning. The command line tool builds
+//! configuration files, plans and interactive prompts on top of the same scan
+use glob::Pattern;
+use humansize::DECIMAL;
+use rayon::prelude::*;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs::{read_dir, Metadata};
+use std::io::{self, ErrorKind};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+pub mod action;
+pub mod activity;
+mod cargo_config;
+pub mod config;
+pub mod detector;
+mod discovery;
+mod error;
+#[cfg(test)]
+mod fixture;
+pub mod goal;
+pub mod interactive;
+mod manifest;
+pub mod plan;
+pub mod policy;
+pub mod quarantine;
+pub mod report;
+mod scanner;
+mod serde_path;
+#[cfg(unix)]
+mod trash;
+mod xdg;
+pub use action::Action;
+use activity::{describe_age, git_activity, newest_source_modification, ActivitySource};
+use detector::Detector;
+use discovery::{Step, TargetJob};
+pub use error::Error;
+use goal::{filesystem_space, Goal};
+use interactive::{Answer, Prompting};
+use manifest::{Project, ProjectKind};
+pub use policy::{Judgement, Policy, SharedPolicy};
+use policy::{MinSize, OlderThan};
+use report::{Decision, FreedAtAge, Record, Summary, TargetEvent};
+pub use scanner::{Scan, Scanner};
+
+/// Settings that apply to the whole scan
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// Target directories with files modified after this are kept, `None` cleans every target directory
+    pub cutoff: Option<SystemTime>,
+    /// What happens to target directories that are old enough
+    pub mode: CleanMode,
+    /// Whether to keep descending into projects that were already found
+    pub nested: bool,
+    /// Which files are checked against `cutoff`
+    pub activity: ActivitySource,
+    /// Directory names skipped when checking project sources for activity
+    pub ignored_dirs: Vec<OsString>,
+    /// Whether the history of the enclosing git repository is checked against `cutoff` too
+    pub git_activity: bool,
+    /// Whether every target directory is measured completely, even if it is kept
+    pub full_report: bool,
+    /// Whether sizes are reported as the lengths of files instead of the space used on disk
+    pub apparent_size: bool,
+    /// Only clean as many of the old enough target directories as needed to reach this
+    pub goal: Option<Goal>,
+    /// Target directories smaller than this many bytes are kept
+    pub min_size: u64,
+    /// Decides about the target directories the other options would clean, which are kept if it
+    /// keeps them
+    pub policy: Option<SharedPolicy>,
+    /// Directories matching any of these aren't scanned
+    pub excludes: Vec<Pattern>,
+    /// What finds projects and their artifact directories
+    pub detectors: Vec<&'static dyn Detector>,
+    /// Whether to ask before cleaning each target directory
+    pub interactive: bool,
+    /// Project directories that were pinned in an interactive run, which are never cleaned
+    pub pins: HashSet<PathBuf>,
+    /// When the scan started, which groups everything quarantined in one run together
+    pub started: SystemTime,
+}
+impl Default for ScanOptions {
+    /// Measures every target directory completely without cleaning any of them
+    fn default() -> Self {
+        ScanOptions {
+            cutoff: None,
+            mode: CleanMode::DryRun,
+            nested: false,
+            activity: ActivitySource::Target,
+            ignored_dirs: vec![OsString::from(".git")],
+            git_activity: false,
+            full_report: true,
+            apparent_size: false,
+            goal: None,
+            min_size: 0,
+            policy: None,
+            excludes: Vec::new(),
+            detectors: detector::BUILTIN.to_vec(),
+            interactive: false,
+            pins: HashSet::new(),
+            started: SystemTime::now(),
+        }
+    }
+}
+impl ScanOptions {
+    /// Picks the size that is reported out of the space used on disk and the apparent size
+    pub fn size_of(&self, size: u64, apparent_size: u64) -> u64 {
+        if self.apparent_size {
+            apparent_size
+        } else {
+            size
+        }
+    }
+}
+/// What is done with target directories that should be cleaned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CleanMode {
+    /// Only list them
+    DryRun,
+    /// Delete them permanently
+    Delete,
+    /// Move them to the freedesktop.org trash
+    Trash,
+    /// Move them into the tool's own quarantine
+    Quarantine,
+}
+impl CleanMode {
+    /// Returns the name used in JSON output
+    pub fn name(self) -> &'static str {
+        match self {
+            CleanMode::DryRun => "dry_run",
+            CleanMode::Delete => "delete",
+            CleanMode::Trash => "trash",
+            CleanMode::Quarantine => "quarantine",
+        }
+    }
+    /// Returns the verb used in summaries, such as "Deleted"
+    pub fn past_tense(self) -> &'static str {
+        match self {
+            CleanMode::DryRun | CleanMode::Delete => "Deleted",
+            CleanMode::Trash => "Trashed",
+            CleanMode::Quarantine => "Quarantined",
+        }
+    }
+}
+/// A file that protects the target directory of the project it is in from being cleaned
+const KEEP_MARKER: &str = ".keep-target";
+/// Values of `--days-old` that a full report shows the effect of
+const REPORT_AGES: [u64; 3] = [7, 30, 90];
+/// What has been seen so far during a scan
+#[derive(Debug, Default)]
+pub struct ScanState {
+    /// Directories that have been scanned for projects
+    pub visited_dirs: HashSet<DirKey>,
+    /// Target directories that have already been checked for cleaning
+    pub cleaned_targets: HashSet<DirKey>,
+    /// Target directories that were cleaned, or would have been in a dry run
+    pub candidates: Vec<Candidate>,
+    /// How many target directories were kept
+    pub kept: usize,
+    /// How many target directories were kept because their project asked for that
+    pub protected: usize,
+    /// Whether the interactive prompt still has anything to ask
+    pub prompting: Prompting,
+    /// Size and unix time of the last activity of every target directory that was fully measured,
+    /// only collected for a full report
+    pub measured: Vec<(u64, Option<u64>)>,
+    /// Files with several hardlinks that have been counted already, so that they are only counted once
+    pub seen_files: HashSet<FileKey>,
+    /// Files with several hardlinks in the target directories that were cleaned, with how many of
+    /// their hardlinks those hold between them
+    pub cleaned_links: HashMap<FileKey, Hardlink>,
+    /// Target directories that are old enough, waiting to be ranked against a goal, along with
+    /// their last activity
+    pub deferred: Vec<(Candidate, Option<SystemTime>)>,
+    /// How many errors scans reported
+    pub errors: usize,
+}
+impl ScanState {
+    /// Records files with several hardlinks as counted, returning the space used on disk and the
+    /// apparent size of those that were counted before
+    pub fn count_hardlinks(&mut self, hardlinks: &mut [Hardlink]) -> (u64, u64) {
+        hardlinks
+            .iter_mut()
+            .filter_map(|hardlink| {
+                hardlink.counted = self.seen_files.insert(hardlink.key);
+                (!hardlink.counted).then_some(&*hardlink)
+            })
+            .fold((0, 0), |(size, apparent_size), hardlink| {
+                (size + hardlink.size, apparent_size + hardlink.apparent_size)
+            })
+    }
+    /// Records the files with several hardlinks in a target directory that was cleaned
+    fn clean_hardlinks(&mut self, hardlinks: &[Hardlink]) {
+        for hardlink in hardlinks {
+            self.cleaned_links
+                .entry(hardlink.key)
+                .and_modify(|cleaned| {
+                    cleaned.seen += hardlink.seen;
+                    cleaned.counted |= hardlink.counted;
+                })
+                .or_insert(*hardlink);
+        }
+    }
+    /// Returns the space used on disk and the apparent size of the files in cleaned target
+    /// directories that are still there, as some of their hardlinks are elsewhere
+    pub fn linked_elsewhere(&self) -> (u64, u64) {
+        self.cleaned_links
+            .values()
+            .filter(|hardlink| hardlink.counted && hardlink.seen < hardlink.links)
+            .fold((0, 0), |(size, apparent_size), hardlink| {
+                (size + hardlink.size, apparent_size + hardlink.apparent_size)
+            })
+    }
+    /// Totals up a finished scan that started at `start_time`
+    pub fn summary(&self, options: &ScanOptions, start_time: Instant) -> Summary {
+        let freed_at_age = if options.full_report {
+            REPORT_AGES
+                .iter()
+                .map(|&days| {
+                    let cutoff = unix_secs(options.started) - days * 3600 * 24;
+                    let old = || {
+                        self.measured
+                            .iter()
+                            .filter(move |(_, last_activity)| last_activity.unwrap_or(0) <= cutoff)
+                    };
+                    FreedAtAge {
+                        days,
+                        targets: old().count(),
+                        bytes: old().map(|(size, _)| size).sum(),
+                    }
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let (linked_size, linked_apparent_size) = self.linked_elsewhere();
+        let disk_bytes: u64 = self.candidates.iter().map(|c| c.size).sum();
+        let apparent_bytes: u64 = self.candidates.iter().map(|c| c.apparent_size).sum();
+        Summary {
+            mode: options.mode.name(),
+            cleaned: self.candidates.len(),
+            kept: self.kept,
+            protected: self.protected,
+            errors: self.errors,
+            bytes: options.size_of(disk_bytes, apparent_bytes)
+                - options.size_of(linked_size, linked_apparent_size),
+            disk_bytes: disk_bytes - linked_size,
+            apparent_bytes: apparent_bytes - linked_apparent_size,
+            linked_bytes: options.size_of(linked_size, linked_apparent_size),
+            seconds: start_time.elapsed().as_secs_f32(),
+            roots: Vec::new(),
+            freed_at_age,
+        }
+    }
+}
+/// A target directory that is old enough to be cleaned
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Candidate {
+    #[serde(with = "serde_path")]
+    pub target_path: PathBuf,
+    /// The project the space is credited to
+    #[serde(with = "serde_path")]
+    pub project_dir: PathBuf,
+    /// Description of the project, such as "package foo"
+    pub project: String,
+    /// Whether this is a leftover target directory of a workspace member
+    pub stray: bool,
+    /// Space used on disk, in bytes
+    pub size: u64,
+    /// Sum of the lengths of all files, in bytes
+    #[serde(default)]
+    pub apparent_size: u64,
+    /// Unix time of the newest modification inside the target directory
+    pub newest_modified: Option<u64>,
+    /// Nanoseconds past `newest_modified` of that modification, so that a plan notices changes
+    /// made within the same second
+    #[serde(default)]
+    pub newest_modified_nanos: u32,
+    /// Unix time of the newest activity that was found, in the target directory or elsewhere
+    #[serde(default)]
+    pub last_activity: Option<u64>,
+    /// Build system the target directory belongs to, such as "cargo"
+    #[serde(default = "cargo_kind")]
+    pub kind: String,
+    /// Why the target directory is being cleaned
+    pub reasons: Vec<String>,
+    /// Files with several hardlinks in the target directory, which are only freed once all of
+    /// their hardlinks are cleaned
+    #[serde(skip)]
+    pub hardlinks: Vec<Hardlink>,
+}
+fn cargo_kind() -> String {
+    "cargo".to_owned()
+}
+/// Size and age of everything in a target directory
+#[derive(Debug, Clone, Default)]
+pub struct TargetStats {
+    /// Space used on disk, in bytes, counting files with several hardlinks once
+    pub size: u64,
+    /// Sum of the lengths of all files, in bytes, counting files with several hardlinks once
+    pub apparent_size: u64,
+    /// Modification time of the newest file or directory, `None` if none could be read
+    pub newest: Option<SystemTime>,
+    /// Files with several hardlinks, which may be counted by other target directories as well
+    pub hardlinks: Vec<Hardlink>,
+}
+/// A file with several hardlinks
+#[derive(Debug, Clone, Copy)]
+pub struct Hardlink {
+    pub key: FileKey,
+    pub size: u64,
+    pub apparent_size: u64,
+    /// How many hardlinks the file has
+    pub links: u64,
+    /// How many of its hardlinks were found in the target directory
+    pub seen: u64,
+    /// Whether the size of the target directory includes the file, which it doesn't if another
+    /// target directory counted it first
+    pub counted: bool,
+}
+/// Identifies a file by device and inode
+pub type FileKey = (u64, u64);
+/// Identifies a physical directory, no matter which path it was reached through
+#[cfg(unix)]
+pub type DirKey = (u64, u64);
+#[cfg(not(unix))]
+pub type DirKey = PathBuf;
+
+/// Returns the key of the directory at `path`, following symlinks
+#[cfg(unix)]
+pub fn dir_key(path: &Path) -> io::Result<DirKey> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = std::fs::metadata(path)?;
+    Ok((metadata.dev(), metadata.ino()))
+}
+/// Returns the key of the directory at `path`, following symlinks
+#[cfg(not(unix))]
+pub fn dir_key(path: &Path) -> io::Result<DirKey> {
+    path.canonicalize()
+}
+
+/// What walking a target directory found out
+#[derive(Debug)]
+pub enum TargetCheck {
+    /// Nothing in it was modified after the cutoff, along with what couldn't be read without that
+    /// mattering, such as a directory that can't be listed and so counts as empty
+    Old(TargetStats, Vec<Error>),
+    /// Something in it was modified after the cutoff
+    Recent,
+    /// Something in it couldn't be read, so it is better left alone
+    Unreadable(Vec<Error>),
+}
+/// Walks the target dir to find out whether it should be deleted, that is if nothing in it was
+/// modified after `cutoff`
+///
+/// Subdirectories are walked in parallel, and the walk stops as soon as anything recent is found
+pub fn check_target_dir_date(dir: &Path, cutoff: Option<SystemTime>) -> TargetCheck {
+    let metadata = match std::fs::metadata(dir) {
+        Ok(v) => v,
+        Err(e) => {
+            return TargetCheck::Unreadable(vec![Error::Scan {
+                path: dir.to_path_buf(),
+                kind: e.kind(),
+                message: format!(
+                    "Error accessing metadata of file {}: {e}, skipping cleaning folder {}",
+                    dir.display(),
+                    dir.display()
+                ),
+            }]);
+        }
+    };
+    let found_recent = AtomicBool::new(false);
+    let mut walk = walk_target_dir(dir, &metadata, dir, cutoff, &found_recent);
+    if found_recent.into_inner() {
+        return TargetCheck::Recent;
+    }
+    // Workers finish in any order, so errors are returned sorted by path
+    walk.errors.sort_by(|a, b| a.path.cmp(&b.path));
+    let unreadable = walk.errors.iter().any(|error| error.unreadable);
+    let errors = walk
+        .errors
+        .into_iter()
+        .map(|error| Error::Scan {
+            message: if error.kind == ErrorKind::Unsupported {
+                error.unsupported.to_owned()
+            } else {
+                error.message
+            },
+            path: error.path,
+            kind: error.kind,
+        })
+        .collect();
+    if unreadable {
+        return TargetCheck::Unreadable(errors);
+    }
+    // The same file can be reached through several of its hardlinks within one target directory
+    walk.hardlinks.sort_by_key(|hardlink| hardlink.key);
+    walk.hardlinks.dedup_by(|hardlink, first| {
+        let same = hardlink.key == first.key;
+        if same {
+            first.seen += hardlink.seen;
+        }
+        same
+    });
+    let stats = TargetStats {
+        size: walk.size + walk.hardlinks.iter().map(|h| h.size).sum::<u64>(),
+        apparent_size: walk.apparent_size
+            + walk.hardlinks.iter().map(|h| h.apparent_size).sum::<u64>(),
+        newest: walk.newest,
+        hardlinks: walk.hardlinks,
+    };
+    TargetCheck::Old(stats, errors)
+}
+/// Totals of part of a target directory
+#[derive(Debug, Default)]
+struct TargetWalk {
+    /// Space used on disk by everything but files with several hardlinks
+    size: u64,
+    /// Lengths of all files but those with several hardlinks
+    apparent_size: u64,
+    newest: Option<SystemTime>,
+    hardlinks: Vec<Hardlink>,
+    errors: Vec<WalkError>,
+}
+impl TargetWalk {
+    fn error(error: WalkError) -> Self {
+        TargetWalk {
+            errors: vec![error],
+            ..Default::default()
+        }
+    }
+    fn merge(mut self, other: TargetWalk) -> Self {
+        self.size += other.size;
+        self.apparent_size += other.apparent_size;
+        self.newest = self.newest.max(other.newest);
+        self.hardlinks.extend(other.hardlinks);
+        self.errors.extend(other.errors);
+        self
+    }
+}
+/// An error that happened while walking a target directory, reported once the walk is over
+#[derive(Debug)]
+struct WalkError {
+    path: PathBuf,
+    kind: ErrorKind,
+    message: String,
+    /// What is reported instead if `kind` is `Unsupported`
+    unsupported: &'static str,
+    /// Whether the target directory has to be kept because of this
+    unreadable: bool,
+}
+fn walk_target_dir(
+    path: &Path,
+    metadata: &Metadata,
+    dir: &Path,
+    cutoff: Option<SystemTime>,
+    found_recent: &AtomicBool,
+) -> TargetWalk {
+    let mut walk = TargetWalk::default();
+    match metadata.modified() {
+        Ok(time) => {
+            if cutoff.is_some_and(|cutoff| time > cutoff) {
+                found_recent.store(true, Ordering::Relaxed);
+                return walk;
+            }
+            walk.newest = Some(time);
+        }
+        Err(e) => {
+            if e.kind() == ErrorKind::Unsupported {
+                walk.errors.push(WalkError {
+                    path: path.to_path_buf(),
+                    kind: e.kind(),
+                    message: e.to_string(),
+                    unsupported:
+                        "This platform does not support finding the modification date of files!",
+                    unreadable: true,
+                });
+            }
+        }
+    }
+    let apparent_size = if metadata.is_file() {
+        metadata.len()
+    } else {
+        0
+    };
+    match hardlink_key(metadata) {
+        Some((key, links)) => walk.hardlinks.push(Hardlink {
+            key,
+            size: disk_usage(metadata),
+            apparent_size,
+            links,
+            seen: 1,
+            counted: false,
+        }),
+        None => {
+            walk.size += disk_usage(metadata);
+            walk.apparent_size += apparent_size;
+        }
+    }
+    if !metadata.is_dir() {
+        return walk;
+    }
+    let entries = match read_dir(path) {
+        Ok(entries) => entries.collect::<Vec<_>>(),
+        Err(e) => {
+            return walk.merge(TargetWalk::error(WalkError {
+                path: path.to_path_buf(),
+                kind: e.kind(),
+                message: format!("Error accessing entry in folder: {}: {e}", path.display()),
+                unsupported: "This platform does not support reading directories!",
+                unreadable: false,
+            }))
+        }
+    };
+    entries
+        .into_par_iter()
+        .map(|entry| {
+            // Nothing else matters once the target directory is known to be kept
+            if found_recent.load(Ordering::Relaxed) {
+                return TargetWalk::default();
+            }
+            let entry = match entry {
+                Ok(v) => v,
+                Err(e) => {
+                    return TargetWalk::error(WalkError {
+                        path: path.to_path_buf(),
+                        kind: e.kind(),
+                        message: format!("Error accessing entry in folder: {e}"),
+                        unsupported: "This platform does not support reading directories!",
+                        unreadable: false,
+                    })
+                }
+            };
+            let entry_path = entry.path();
+            match entry.metadata() {
+                Ok(metadata) => walk_target_dir(&entry_path, &metadata, dir, cutoff, found_recent),
+                Err(e) => TargetWalk::error(WalkError {
+                    message: format!(
+                        "Error accessing metadata of file {}: {e}, skipping cleaning folder {}",
+                        entry_path.display(),
+                        dir.display()
+                    ),
+                    path: entry_path,
+                    kind: e.kind(),
+                    unsupported:
+                        "This platform does not support finding the metadata date of files!",
+                    unreadable: true,
+                }),
+            }
+        })
+        .reduce(TargetWalk::default, TargetWalk::merge)
+        .merge(walk)
+}
+/// Returns the space a file takes up on disk, which is less than its length for sparse files
+#[cfg(unix)]
+fn disk_usage(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    // st_blocks is always in units of 512 bytes, whatever the block size of the filesystem
+    metadata.blocks() * 512
+}
+#[cfg(not(unix))]
+fn disk_usage(metadata: &Metadata) -> u64 {
+    if metadata.is_file() {
+        metadata.len()
+    } else {
+        0
+    }
+}
+/// Returns the key and the number of hardlinks of a file that has several of them
+#[cfg(unix)]
+fn hardlink_key(metadata: &Metadata) -> Option<(FileKey, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.is_file() && metadata.nlink() > 1)
+        .then(|| ((metadata.dev(), metadata.ino()), metadata.nlink()))
+}
+#[cfg(not(unix))]
+fn hardlink_key(_metadata: &Metadata) -> Option<(FileKey, u64)> {
+    None
+}
+/// What was decided about a target directory, before anything is done with it
+pub enum Verdict {
+    Clean {
+        candidate: Candidate,
+        /// The newest activity that was found
+        last_activity: Option<SystemTime>,
+        hardlinks: Vec<Hardlink>,
+    },
+    Keep {
+        event: TargetEvent,
+        text: Option<String>,
+        /// Files with several hardlinks, if the target directory was measured
+        hardlinks: Vec<Hardlink>,
+    },
+}
+/// Scans `root` for target directories and deletes those that are old enough, returning the
+/// number of bytes freed
+///
+/// Target directories are checked in parallel, but reported and cleaned in the order they were
+/// found. Everything the scan reports is handed to `sink` as it happens
+pub fn scan_for_target_dirs(
+    root: PathBuf,
+    options: &ScanOptions,
+    state: &mut ScanState,
+    mut sink: impl FnMut(Record) + 'static,
+) -> u64 {
+    let errors = Rc::new(Cell::new(0));
+    let counted = errors.clone();
+    let sink = move |record: Record| {
+        if record.is_error() {
+            counted.set(counted.get() + 1);
+        }
+        sink(record);
+    };
+    let total_size = report::forward(sink, || scan(root, options, state));
+    state.errors += errors.get();
+    total_size
+}
+fn scan(root: PathBuf, options: &ScanOptions, state: &mut ScanState) -> u64 {
+    let steps = discovery::discover(root, options, state);
+    let verdicts: Vec<_> = steps
+        .par_iter()
+        .map(|step| match step {
+            Step::Target(job) => Some(report::capture(|| check_target(job, options))),
+            Step::Records(_) => None,
+        })
+        .collect();
+    let mut total_size = 0;
+    for (step, verdict) in steps.into_iter().zip(verdicts) {
+        match (step, verdict) {
+            (Step::Records(records), _) => report::replay(records),
+            (Step::Target(_), Some((verdict, records))) => {
+                report::replay(records);
+                total_size += clean_target_dir(verdict, options, state);
+            }
+            (Step::Target(_), None) => unreachable!(),
+        }
+    }
+    if let Some(goal) = options.goal {
+        total_size += clean_to_goal(goal, options, state);
+    }
+    total_size
+}
+/// Checks whether the target directory of a project is old enough to be cleaned
+pub(crate) fn check_target(job: &TargetJob, options: &ScanOptions) -> Verdict {
+    let TargetJob {
+        target_path,
+        project,
+        stray,
+        kind,
+        shared_with,
+        ..
+    } = job;
+    let projects: Vec<_> = iter::once(project).chain(shared_with).collect();
+    let stray = *stray;
+    let mut candidate = Candidate {
+        target_path: target_path.clone(),
+        project_dir: project.dir.clone(),
+        project: project.describe(),
+        stray,
+        size: 0,
+        apparent_size: 0,
+        newest_modified: None,
+        newest_modified_nanos: 0,
+        last_activity: None,
+        kind: (*kind).to_owned(),
+        reasons: Vec::new(),
+        hardlinks: Vec::new(),
+    };
+    if stray {
+        candidate.reasons.push(
+            "stray target directory left over from building a workspace member on its own"
+                .to_owned(),
+        );
+    }
+    if !shared_with.is_empty() {
+        let others: Vec<_> = shared_with.iter().map(Project::describe).collect();
+        candidate.reasons.push(format!(
+            "the target directory is shared with {}, whose activity counts too",
+            others.join(", ")
+        ));
+    }
+    // Every project sharing the target directory can protect it
+    let markers = projects.iter().flat_map(|project| match &project.kind {
+        ProjectKind::Member(workspace_root) => vec![&project.dir, workspace_root],
+        _ => vec![&project.dir],
+    });
+    if let Some(marker) = markers
+        .map(|dir| dir.join(KEEP_MARKER))
+        .find(|marker| marker.exists())
+    {
+        let reason = format!("{} exists", marker.display());
+        return protect_target(candidate, reason);
+    }
+    if projects
+        .iter()
+        .any(|project| options.pins.contains(&project.dir))
+    {
+        return protect_target(candidate, "it was pinned in an interactive run".to_owned());
+    }
+    let mut cutoff = options.cutoff;
+    // The project asking to keep things around the longest wins
+    let mut days_old = None;
+    for settings in projects
+        .iter()
+        .filter_map(|project| project.settings.as_ref())
+    {
+        if settings.keep {
+            return protect_target(candidate, format!("keep is set in {}", settings.source));
+        }
+        if let Some(days) = settings.days_old {
+            if days_old.is_none_or(|(longest, _)| days > longest) {
+                days_old = Some((days, &settings.source));
+            }
+        }
+    }
+    if let Some((days_old, source)) = days_old {
+        candidate
+            .reasons
+            .push(format!("days-old is {days_old} in {source}"));
+        cutoff = cutoff_for(days_old, options.started);
+    }
+    // With a full report everything is measured, and the first reason to keep is only used at the
+    // end. Goals need to know the size and last activity of everything as well
+    let full = options.full_report || options.goal.is_some();
+    let days = cutoff.map_or(0, |cutoff| {
+        options
+            .started
+            .duration_since(cutoff)
+            .unwrap_or_default()
+            .as_secs()
+            / (3600 * 24)
+    });
+    // Each kind of activity is judged as soon as it is found, so that measuring can stop early
+    let older_than = cutoff.map(|cutoff| OlderThan {
+        days,
+        cutoff: unix_secs(cutoff),
+    });
+    let is_recent = |time: Option<SystemTime>| {
+        older_than.is_some_and(|policy| policy.is_recent(time.map(unix_secs)))
+    };
+    let mut keep = None;
+    let mut last_activity = None;
+    if options.git_activity && (cutoff.is_some() || full) {
+        let newest = projects
+            .iter()
+            .filter_map(|project| git_activity(&project.dir))
+            .max_by_key(|(time, _)| *time);
+        if let Some((time, signal)) = newest {
+            let reason = format!("{} was {}", signal, describe_age(time));
+            last_activity = Some(time);
+            if is_recent(Some(time)) {
+                let text = format!(
+                    "Keeping {}target directory {} of {}, {}",
+                    if stray { "stray " } else { "" },
+                    candidate.target_path.display(),
+                    candidate.project,
+                    reason
+                );
+                if !full {
+                    return keep_target(candidate, None, Some(time), reason, Some(text));
+                }
+                keep = Some((reason, Some(text)));
+            } else {
+                candidate.reasons.push(reason);
+            }
+        }
+    }
+    if options.activity.includes_sources() && (cutoff.is_some() || full) {
+        let newest = newest_source_modification(
+            &job.activity,
+            &job.artifacts,
+            cutoff.filter(|_| !full),
+            &options.ignored_dirs,
+        );
+        if is_recent(newest) {
+            let reason =
+                format!("something in the project sources was modified in the last {days} days");
+            if !full {
+                return keep_target(candidate, None, None, reason, None);
+            }
+            keep.get_or_insert((reason, None));
+        } else if cutoff.is_some() {
+            candidate.reasons.push(format!(
+                "nothing in the project sources was modified in the last {days} days"
+            ));
+        }
+        last_activity = last_activity.max(newest);
+    }
+    let target_cutoff = cutoff.filter(|_| options.activity.includes_target());
+    // The fast path stops walking at the first recent file, leaving the size of kept ones unknown
+    let walk_cutoff = target_cutoff.filter(|_| !full);
+    let report_errors = |errors: Vec<Error>| {
+        for error in errors {
+            report::error(error.path(), error.kind(), &error);
+        }
+    };
+    let stats = match check_target_dir_date(&candidate.target_path, walk_cutoff) {
+        TargetCheck::Old(stats, errors) => {
+            report_errors(errors);
+            stats
+        }
+        TargetCheck::Recent => {
+            let reason =
+                format!("something in the target directory was modified in the last {days} days");
+            return keep_target(candidate, None, None, reason, None);
+        }
+        TargetCheck::Unreadable(errors) => {
+            report_errors(errors);
+            let reason = "something in the target directory couldn't be read".to_owned();
+            return keep_target(candidate, None, None, reason, None);
+        }
+    };
+    if options.activity.includes_target() {
+        if is_recent(stats.newest) {
+            let reason =
+                format!("something in the target directory was modified in the last {days} days");
+            keep.get_or_insert((reason, None));
+        } else if cutoff.is_some() {
+            candidate.reasons.push(format!(
+                "nothing in the target directory was modified in the last {days} days"
+            ));
+        }
+        last_activity = last_activity.max(stats.newest);
+    }
+    candidate.size = stats.size;
+    candidate.apparent_size = stats.apparent_size;
+    candidate.newest_modified = stats.newest.map(unix_secs);
+    candidate.newest_modified_nanos = stats.newest.map_or(0, subsec_nanos);
+    candidate.last_activity = last_activity.map(unix_secs);
+    // The policies have the final say on the measured target directory
+    let mut policies: Vec<Box<dyn Policy>> = Vec::new();
+    if let Some(older_than) = older_than {
+        policies.push(Box::new(older_than));
+    }
+    if options.min_size > 0 {
+        policies.push(Box::new(MinSize {
+            bytes: options.min_size,
+            apparent_size: options.apparent_size,
+        }));
+    }
+    if let Some(policy) = &options.policy {
+        policies.push(Box::new(policy.clone()));
+    }
+    if let Judgement::Keep(reason) = policies.judge(&candidate) {
+        keep.get_or_insert((reason, None));
+    }
+    if let Some((reason, text)) = keep {
+        return keep_target(candidate, Some(stats), last_activity, reason, text);
+    }
+    if cutoff.is_none() && options.goal.is_none() {
+        candidate
+            .reasons
+            .push("every target directory is cleaned when --days-old is 0".to_owned());
+    }
+    Verdict::Clean {
+        candidate,
+        last_activity,
+        hardlinks: stats.hardlinks,
+    }
+}
+/// Reports what was decided about a target directory and deletes it if it is old enough,
+/// returning the number of bytes freed
+pub fn clean_target_dir(verdict: Verdict, options: &ScanOptions, state: &mut ScanState) -> u64 {
+    let (candidate, last_activity) = match verdict {
+        Verdict::Clean {
+            candidate,
+            last_activity,
+            mut hardlinks,
+        } => {
+            let (size, apparent_size) = state.count_hardlinks(&mut hardlinks);
+            (
+                Candidate {
+                    size: candidate.size - size,
+                    apparent_size: candidate.apparent_size - apparent_size,
+                    hardlinks,
+                    ..candidate
+                },
+                last_activity,
+            )
+        }
+        Verdict::Keep {
+            mut event,
+            text,
+            mut hardlinks,
+        } => {
+            match event.decision {
+                Decision::Protected => state.protected += 1,
+                _ => state.kept += 1,
+            }
+            let (size, apparent_size) = state.count_hardlinks(&mut hardlinks);
+            event.size = event.size.map(|total| total - size);
+            event.apparent_size = event.apparent_size.map(|total| total - apparent_size);
+            if let (true, Some(size), Some(apparent_size)) =
+                (options.full_report, event.size, event.apparent_size)
+            {
+                let size = options.size_of(size, apparent_size);
+                state.measured.push((size, event.last_activity));
+            }
+            report::target(event, text);
+            return 0;
+        }
+    };
+    if options.goal.is_some() {
+        state.deferred.push((candidate, last_activity));
+        return 0;
+    }
+    clean_candidate_dir(candidate, last_activity, options, state)
+}
+/// Reports that a target directory is cleaned and cleans it, returning the number of bytes freed
+fn clean_candidate_dir(
+    candidate: Candidate,
+    last_activity: Option<SystemTime>,
+    options: &ScanOptions,
+    state: &mut ScanState,
+) -> u64 {
+    let size = options.size_of(candidate.size, candidate.apparent_size);
+    if options.interactive {
+        let reason = match confirm(&candidate, size, last_activity, options, state) {
+            Ok(()) => None,
+            Err(Answer::Pin) => match interactive::pin(&candidate.project_dir) {
+                Ok(_) => {
+                    let reason = "it was pinned in an interactive run".to_owned();
+                    return clean_target_dir(protect_target(candidate, reason), options, state);
+                }
+                Err(e) => {
+                    report::error(
+                        &candidate.project_dir,
+                        e.kind(),
+                        format!(
+                            "Error pinning {}, keeping it for now: {}",
+                            candidate.project_dir.display(),
+                            e
+                        ),
+                    );
+                    Some("it was declined interactively")
+                }
+            },
+            Err(_) => Some("it was declined interactively"),
+        };
+        if let Some(reason) = reason {
+            let stats = TargetStats {
+                size: candidate.size,
+                apparent_size: candidate.apparent_size,
+                ..Default::default()
+            };
+            let verdict = keep_target(
+                candidate,
+                Some(stats),
+                last_activity,
+                reason.to_owned(),
+                None,
+            );
+            return clean_target_dir(verdict, options, state);
+        }
+    }
+    if options.full_report {
+        state.measured.push((size, last_activity.map(unix_secs)));
+    }
+    let text = format!(
+        "Deleting {} of files in {}target directory {} of {}",
+        humansize::format_size(size, DECIMAL),
+        if candidate.stray { "stray " } else { "" },
+        candidate.target_path.display(),
+        candidate.project
+    );
+    report::target(
+        TargetEvent {
+            target_path: candidate.target_path.clone(),
+            project_dir: candidate.project_dir.clone(),
+            project: candidate.project.clone(),
+            stray: candidate.stray,
+            kind: candidate.kind.clone(),
+            size: Some(candidate.size),
+            apparent_size: Some(candidate.apparent_size),
+            last_activity: last_activity.map(unix_secs),
+            decision: Decision::Clean,
+            reasons: candidate.reasons.clone(),
+        },
+        Some(text),
+    );
+    if let Err(e) = clean_candidate(
+        &candidate,
+        options.mode,
+        options.started,
+        options.apparent_size,
+    ) {
+        report::error(e.path(), e.kind(), &e);
+        return 0;
+    }
+    state.clean_hardlinks(&candidate.hardlinks);
+    state.candidates.push(candidate);
+    size
+}
+/// Asks whether the target directory of `candidate` should be cleaned, returning the answer if it
+/// shouldn't
+fn confirm(
+    candidate: &Candidate,
+    size: u64,
+    last_activity: Option<SystemTime>,
+    options: &ScanOptions,
+    state: &mut ScanState,
+) -> Result<(), Answer> {
+    match state.prompting {
+        Prompting::YesToAll => return Ok(()),
+        Prompting::NoToAll => return Err(Answer::SkipRest),
+        Prompting::Ask => (),
+    }
+    let question = format!(
+        "{} {}target directory {} of {}? {}, last activity {}",
+        match options.mode {
+            CleanMode::DryRun | CleanMode::Delete => "Delete",
+            CleanMode::Trash => "Trash",
+            CleanMode::Quarantine => "Quarantine",
+        },
+        if candidate.stray { "stray " } else { "" },
+        candidate.target_path.display(),
+        candidate.project,
+        humansize::format_size(size, DECIMAL),
+        last_activity.map_or_else(|| "unknown".to_owned(), describe_age)
+    );
+    match interactive::ask(&question) {
+        Answer::Yes => Ok(()),
+        Answer::All => {
+            state.prompting = Prompting::YesToAll;
+            Ok(())
+        }
+        Answer::SkipRest => {
+            state.prompting = Prompting::NoToAll;
+            Err(Answer::SkipRest)
+        }
+        answer => Err(answer),
+    }
+}
+/// Cleans the least recently used target directories that were deferred until `goal` is reached,
+/// returning the number of bytes freed
+///
+/// Trashing or quarantining within the same filesystem doesn't make space available, so the goal
+/// is tracked as if everything cleaned was deleted
+fn clean_to_goal(goal: Goal, options: &ScanOptions, state: &mut ScanState) -> u64 {
+    let mut deferred = std::mem::take(&mut state.deferred);
+    // The path breaks ties so that the order doesn't depend on how the scan went
+    deferred.sort_by(|(a, a_activity), (b, b_activity)| {
+        a_activity
+            .cmp(b_activity)
+            .then_with(|| a.target_path.cmp(&b.target_path))
+    });
+    let mut total_size = 0;
+    // Bytes available and wanted on each filesystem, `None` if that couldn't be found out
+    let mut filesystems: HashMap<u64, Option<(u64, u64)>> = HashMap::new();
+    for (mut candidate, last_activity) in deferred {
+        let device = device_of(&candidate.target_path);
+        let reason = match goal {
+            Goal::Free(bytes) => {
+                (total_size >= bytes).then(|| format!("the goal of {goal} is reached without it"))
+            }
+            Goal::UntilFree(free) => {
+                let target_path = &candidate.target_path;
+                let space = filesystems.entry(device).or_insert_with(|| {
+                    match filesystem_space(target_path) {
+                        Ok(space) => Some((space.available, free.bytes_of(space.total))),
+                        Err(e) => {
+                            report::error(
+                                target_path,
+                                e.kind(),
+                                format!(
+                                    "Error checking free space of the filesystem holding {}: {}",
+                                    target_path.display(),
+                                    e
+                                ),
+                            );
+                            None
+                        }
+                    }
+                });
+                match space {
+                    Some((available, wanted)) if *available >= *wanted => {
+                        Some(format!("the goal of {goal} is reached without it"))
+                    }
+                    Some(_) => None,
+                    None => Some("the free space of its filesystem couldn't be checked".to_owned()),
+                }
+            }
+        };
+        if let Some(reason) = reason {
+            let stats = TargetStats {
+                size: candidate.size,
+                apparent_size: candidate.apparent_size,
+                ..Default::default()
+            };
+            let verdict = keep_target(candidate, Some(stats), last_activity, reason, None);
+            clean_target_dir(verdict, options, state);
+            continue;
+        }
+        candidate.reasons.push(format!(
+            "among the least recently used target directories needed to reach the goal of {goal}"
+        ));
+        let freed = clean_candidate_dir(candidate, last_activity, options, state);
+        if let Some(Some((available, _))) = filesystems.get_mut(&device) {
+            *available += freed;
+        }
+        total_size += freed;
+    }
+    total_size
+}
+/// Returns the device holding `path`, which tells filesystems apart
+#[cfg(unix)]
+fn device_of(path: &Path) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).map_or(0, |metadata| metadata.dev())
+}
+#[cfg(not(unix))]
+fn device_of(_path: &Path) -> u64 {
+    0
+}
+/// Decides to keep the target directory of `candidate` because its project asked for that
+fn protect_target(candidate: Candidate, reason: String) -> Verdict {
+    let text = format!(
+        "Protecting {}target directory {} of {}, as {}",
+        if candidate.stray { "stray " } else { "" },
+        candidate.target_path.display(),
+        candidate.project,
+        reason
+    );
+    Verdict::Keep {
+        event: TargetEvent {
+            target_path: candidate.target_path,
+            project_dir: candidate.project_dir,
+            project: candidate.project,
+            stray: candidate.stray,
+            kind: candidate.kind,
+            size: None,
+            apparent_size: None,
+            last_activity: None,
+            decision: Decision::Protected,
+            reasons: vec![reason],
+        },
+        text: Some(text),
+        hardlinks: Vec::new(),
+    }
+}
+/// Decides to keep the target directory of `candidate` because of `reason`
+fn keep_target(
+    candidate: Candidate,
+    stats: Option<TargetStats>,
+    last_activity: Option<SystemTime>,
+    reason: String,
+    text: Option<String>,
+) -> Verdict {
+    let stats = stats.as_ref();
+    Verdict::Keep {
+        event: TargetEvent {
+            target_path: candidate.target_path,
+            project_dir: candidate.project_dir,
+            project: candidate.project,
+            stray: candidate.stray,
+            kind: candidate.kind,
+            size: stats.map(|stats| stats.size),
+            apparent_size: stats.map(|stats| stats.apparent_size),
+            last_activity: last_activity.map(unix_secs),
+            decision: Decision::Keep,
+            reasons: vec![reason],
+        },
+        text,
+        hardlinks: stats
+            .map(|stats| stats.hardlinks.clone())
+            .unwrap_or_default(),
+    }
+}
+/// Does what `mode` says with the target directory of `candidate`, returning whether it succeeded
+///
+/// Quarantined directories are recorded with their apparent size if `apparent_size` is set
+pub fn clean_candidate(
+    candidate: &Candidate,
+    mode: CleanMode,
+    started: SystemTime,
+    apparent_size: bool,
+) -> Result<(), Error> {
+    match mode {
+        CleanMode::DryRun => Ok(()),
+        CleanMode::Delete => action::Delete.apply(candidate),
+        CleanMode::Trash => action::Trash.apply(candidate),
+        CleanMode::Quarantine => action::Quarantine {
+            started,
+            apparent_size,
+        }
+        .apply(candidate),
+    }
+}
+/// Converts `time` to seconds since the unix epoch, clamping times before it to 0
+pub fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+/// Returns the nanoseconds of `time` past the second it is in, 0 for times before the unix epoch
+pub fn subsec_nanos(time: SystemTime) -> u32 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0)
+}
+/// Returns when target directories count as old, `None` if all of them do
+pub fn cutoff_for(days_old: usize, now: SystemTime) -> Option<SystemTime> {
+    if days_old == 0 {
+        None
+    } else {
+        Some(now - Duration::from_secs((3600 * 24 * days_old) as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{add, tree};
+    use report::Event;
+    use std::cell::RefCell;
+    use std::fs::hard_link;
+
+    /// Length of the hardlinked files, which is bigger than a block so that every file takes up
+    /// space on disk
+    const FILE_LEN: usize = 64 * 1024;
+
+    fn package(name: &str) -> (String, String) {
+        (
+            format!("{name}/Cargo.toml"),
+            format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\n"),
+        )
+    }
+
+    /// Makes Cargo projects with one file each in their target directories
+    fn projects(names: &[&str]) -> (tempfile::TempDir, PathBuf) {
+        let contents = "x".repeat(FILE_LEN);
+        let mut files: Vec<_> = names.iter().map(|name| package(name)).collect();
+        files.extend(
+            names
+                .iter()
+                .map(|name| (format!("{name}/target/debug/file"), contents.clone())),
+        );
+        let files: Vec<_> = files
+            .iter()
+            .map(|(path, contents)| (path.as_str(), contents.as_str()))
+            .collect();
+        let dir = tree(&files);
+        let root = dir.path().canonicalize().unwrap();
+        (dir, root)
+    }
+
+    /// Scans `root` without cleaning anything, returning the scan's state along with what it
+    /// reported
+    fn dry_run(root: &Path, options: &ScanOptions) -> (ScanState, Vec<Record>) {
+        let mut state = ScanState::default();
+        state.visited_dirs.insert(dir_key(root).unwrap());
+        let records = Rc::new(RefCell::new(Vec::new()));
+        let sink = records.clone();
+        scan_for_target_dirs(root.to_path_buf(), options, &mut state, move |record| {
+            sink.borrow_mut().push(record)
+        });
+        let records = records.take();
+        (state, records)
+    }
+
+    fn apparent_sizes(state: &ScanState) -> Vec<u64> {
+        state
+            .candidates
+            .iter()
+            .map(|candidate| candidate.apparent_size)
+            .collect()
+    }
+
+    /// Replaces the file in the target directory of `to` with a hardlink to the one of `from`
+    fn link_files(root: &Path, from: &str, to: &str) {
+        let link = root.join(to).join("target/debug/file");
+        std::fs::remove_file(&link).unwrap();
+        hard_link(root.join(from).join("target/debug/file"), link).unwrap();
+    }
+
+    #[test]
+    fn counts_hardlinks_within_a_target_directory_once() {
+        let (_dir, root) = projects(&["a"]);
+        let target = root.join("a/target/debug");
+        hard_link(target.join("file"), target.join("link")).unwrap();
+        hard_link(target.join("file"), target.join("another link")).unwrap();
+        let (state, _) = dry_run(&root, &ScanOptions::default());
+        assert_eq!(apparent_sizes(&state), [FILE_LEN as u64]);
+        let summary = state.summary(&ScanOptions::default(), Instant::now());
+        assert_eq!(summary.apparent_bytes, FILE_LEN as u64);
+        assert_eq!(summary.linked_bytes, 0);
+        assert_eq!(summary.bytes, summary.disk_bytes);
+    }
+
+    #[test]
+    fn leaves_files_linked_from_outside_out_of_what_is_freed() {
+        let (_dir, root) = projects(&["a", "b"]);
+        hard_link(root.join("a/target/debug/file"), root.join("outside")).unwrap();
+        let options = ScanOptions {
+            apparent_size: true,
+            ..Default::default()
+        };
+        let (state, _) = dry_run(&root, &options);
+        // The target directory still holds the file, it just isn't freed by cleaning it
+        assert_eq!(apparent_sizes(&state), [FILE_LEN as u64, FILE_LEN as u64]);
+        let summary = state.summary(&options, Instant::now());
+        assert_eq!(summary.linked_bytes, FILE_LEN as u64);
+        assert_eq!(summary.apparent_bytes, FILE_LEN as u64);
+        assert_eq!(summary.bytes, FILE_LEN as u64);
+        // On disk, the file still takes up its blocks
+        let file_size = disk_usage(&std::fs::metadata(root.join("outside")).unwrap());
+        let summary = state.summary(&ScanOptions::default(), Instant::now());
+        assert_eq!(summary.linked_bytes, file_size);
+        let sizes: u64 = state
+            .candidates
+            .iter()
+            .map(|candidate| candidate.size)
+            .sum();
+        assert_eq!(summary.disk_bytes, sizes - file_size);
+    }
+
+    #[test]
+    fn frees_files_linked_between_target_directories_once_all_are_cleaned() {
+        let (_dir, root) = projects(&["a", "b", "c"]);
+        link_files(&root, "a", "b");
+        let options = ScanOptions {
+            apparent_size: true,
+            ..Default::default()
+        };
+        let (state, _) = dry_run(&root, &options);
+        // The first target directory to find the file counts it
+        assert_eq!(
+            apparent_sizes(&state),
+            [FILE_LEN as u64, 0, FILE_LEN as u64]
+        );
+        let summary = state.summary(&options, Instant::now());
+        assert_eq!(summary.linked_bytes, 0);
+        assert_eq!(summary.bytes, 2 * FILE_LEN as u64);
+        // Keeping either target directory keeps the file
+        std::fs::write(root.join("b").join(KEEP_MARKER), "").unwrap();
+        let (state, _) = dry_run(&root, &options);
+        assert_eq!(apparent_sizes(&state), [FILE_LEN as u64, FILE_LEN as u64]);
+        let summary = state.summary(&options, Instant::now());
+        assert_eq!(summary.linked_bytes, FILE_LEN as u64);
+        assert_eq!(summary.bytes, FILE_LEN as u64);
+    }
+
+    /// Renders what a scan reported the way the text and jsonl formats print it
+    fn render(records: &[Record]) -> (Vec<String>, Vec<String>) {
+        records
+            .iter()
+            .map(|record| {
+                (
+                    record.text().unwrap_or_default().to_owned(),
+                    serde_json::to_string(record.event()).unwrap(),
+                )
+            })
+            .unzip()
+    }
+
+    #[test]
+    fn reports_in_the_same_order_however_the_scan_is_scheduled() {
+        let mut files = Vec::new();
+        for group in 0..8 {
+            for project in 0..8 {
+                let dir = format!("group{group}/project{project}");
+                files.push((
+                    format!("{dir}/Cargo.toml"),
+                    package(&dir.replace('/', "-")).1,
+                ));
+                files.push((
+                    format!("{dir}/target/debug/output"),
+                    "x".repeat(project * 100),
+                ));
+                files.push((format!("{dir}/web/package-lock.json"), String::new()));
+                files.push((format!("{dir}/web/package.json"), "{}".to_owned()));
+                files.push((
+                    format!("{dir}/web/node_modules/dep/index.js"),
+                    String::new(),
+                ));
+            }
+        }
+        let files: Vec<_> = files
+            .iter()
+            .map(|(path, contents)| (path.as_str(), contents.as_str()))
+            .collect();
+        let dir = tree(&files);
+        let root = dir.path().canonicalize().unwrap();
+        #[cfg(unix)]
+        for group in 0..8 {
+            let link = root.join(format!("group{group}/link"));
+            std::os::unix::fs::symlink(root.join(format!("group{}", 7 - group)), link).unwrap();
+        }
+        let options = ScanOptions {
+            nested: true,
+            ..Default::default()
+        };
+        let sequential = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap()
+            .install(|| dry_run(&root, &options).1);
+        let (text, jsonl) = render(&sequential);
+        assert_eq!(
+            text.iter()
+                .filter(|line| line.starts_with("Deleting"))
+                .count(),
+            128
+        );
+        for _ in 0..5 {
+            let (parallel_text, parallel_jsonl) = render(&dry_run(&root, &options).1);
+            assert_eq!(parallel_text, text);
+            assert_eq!(parallel_jsonl, jsonl);
+        }
+    }
+
+    #[test]
+    fn reports_which_detector_found_each_target_directory() {
+        let (dir, root) = projects(&["a"]);
+        add(
+            dir.path(),
+            &[
+                ("a/web/package.json", "{}"),
+                ("a/web/yarn.lock", ""),
+                ("a/web/node_modules/", ""),
+            ],
+        );
+        let options = ScanOptions {
+            nested: true,
+            ..Default::default()
+        };
+        let (_, records) = dry_run(&root, &options);
+        let kinds: Vec<_> = records
+            .iter()
+            .filter_map(|record| match record.event() {
+                Event::Target(event) => Some(event.kind.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(kinds, ["cargo", "node"]);
+        let json = serde_json::to_value(records[0].event()).unwrap();
+        assert_eq!(json["kind"], "cargo");
+    }
+
+    /// Returns what was decided about each target directory a scan reported, along with why
+    fn decisions(records: &[Record]) -> Vec<(PathBuf, Decision, Vec<String>)> {
+        records
+            .iter()
+            .filter_map(|record| match record.event() {
+                Event::Target(event) => Some((
+                    event.target_path.clone(),
+                    event.decision,
+                    event.reasons.clone(),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn protects_target_directories_with_a_keep_marker() {
+        let (dir, root) = projects(&["a", "b"]);
+        add(
+            dir.path(),
+            &[
+                ("a/.keep-target", ""),
+                ("w/Cargo.toml", "[workspace]\nmembers = [\"m\"]\n"),
+                ("w/.keep-target", ""),
+                ("w/target/", ""),
+                ("w/m/Cargo.toml", &package("m").1),
+                ("w/m/target/", ""),
+            ],
+        );
+        let (state, records) = dry_run(&root, &ScanOptions::default());
+        let marker = |dir: &str| {
+            vec![format!(
+                "{} exists",
+                root.join(dir).join(KEEP_MARKER).display()
+            )]
+        };
+        // Members are protected by the marker of their workspace too
+        assert_eq!(
+            decisions(&records),
+            [
+                (root.join("a/target"), Decision::Protected, marker("a")),
+                (
+                    root.join("b/target"),
+                    Decision::Clean,
+                    vec!["every target directory is cleaned when --days-old is 0".to_owned()],
+                ),
+                (root.join("w/target"), Decision::Protected, marker("w")),
+                (root.join("w/m/target"), Decision::Protected, marker("w")),
+            ]
+        );
+        assert_eq!(state.protected, 3);
+    }
+
+    #[test]
+    fn protects_target_directories_kept_by_their_manifest() {
+        let (dir, root) = projects(&["a"]);
+        add(
+            dir.path(),
+            &[(
+                "a/Cargo.toml",
+                "[package]\nname = \"a\"\nversion = \"0.1.0\"\n\n[package.metadata.cleaner]\nkeep = true\n",
+            )],
+        );
+        let (_, records) = dry_run(&root, &ScanOptions::default());
+        let source = format!(
+            "keep is set in package.metadata.cleaner in {}",
+            root.join("a/Cargo.toml").display()
+        );
+        assert_eq!(
+            decisions(&records),
+            [(root.join("a/target"), Decision::Protected, vec![source])]
+        );
+    }
+
+    #[test]
+    fn protects_target_directories_of_pinned_projects() {
+        let (dir, root) = projects(&["a", "b"]);
+        add(
+            dir.path(),
+            &[
+                (".cargo/config.toml", "[build]\ntarget-dir = \"shared\"\n"),
+                ("shared/", ""),
+                ("c/Cargo.toml", &package("c").1),
+                ("d/Cargo.toml", &package("d").1),
+            ],
+        );
+        let pinned = "it was pinned in an interactive run".to_owned();
+        let options = ScanOptions {
+            pins: HashSet::from([root.join("d")]),
+            ..Default::default()
+        };
+        let (state, records) = dry_run(&root, &options);
+        // Projects sharing a target directory can each protect it
+        assert_eq!(
+            decisions(&records),
+            [(
+                root.join("shared"),
+                Decision::Protected,
+                vec![pinned.clone()]
+            )]
+        );
+        assert_eq!(state.protected, 1);
+        // Only project directories are pinned
+        let options = ScanOptions {
+            pins: HashSet::from([root.join("shared")]),
+            ..Default::default()
+        };
+        let (_, records) = dry_run(&root, &options);
+        assert_eq!(decisions(&records)[0].1, Decision::Clean);
+    }
+}
@@ -1,233 +1,822 @@
+use glob::Pattern;
 use humansize::DECIMAL;
+use std::collections::HashSet;
 use std::ffi::OsString;
-use std::fs::{read_dir, read_link};
 use std::io::{self, ErrorKind};
 use std::path::{Path, PathBuf};
-use std::time::{Instant, SystemTime};
-use walkdir::WalkDir;
+use std::time::{Duration, Instant, SystemTime};
 
-use clap::Parser;
+use clap::{CommandFactory, Parser, Subcommand};
+
+use code_workspaces_cleaner_upper::activity::ActivitySource;
+use code_workspaces_cleaner_upper::detector::{self, Detector};
+use code_workspaces_cleaner_upper::goal::{parse_free_space, parse_size, FreeSpace, Goal};
+use code_workspaces_cleaner_upper::plan::{self, Plan};
+use code_workspaces_cleaner_upper::report::{Decision, Event, FreedAtAge, Record, RootSummary};
+use code_workspaces_cleaner_upper::{
+    clean_candidate, config, cutoff_for, dir_key, interactive, quarantine, scan_for_target_dirs,
+    unix_secs, CleanMode, ScanOptions, ScanState, Scanner,
+};
+
+mod output;
+mod tui;
+
+use output::OutputFormat;
 
 /*
 Process:
 * Start in the root directory
-* Recursively iterate through directories, if they contain both a Cargo.toml and a target/ directory,
-  * Check the modification date of the most recently modified file in the folder, if older than a certain number of days, delete the target directory
+* Recursively iterate through directories, if they contain a Cargo.toml and the target directory cargo would use for it exists,
+  or the manifest and build output of one of the other build systems (see the detector module),
+  * Check the modification date of the most recently modified file in the target folder and/or the project sources, if older than a certain number of days, delete the target directory
  */
 
 /// Simple program to greet a person
 #[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
+#[command(
+    version,
+    about,
+    long_about = None,
+    subcommand_negates_reqs = true,
+    args_conflicts_with_subcommands = true
+)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    scan: ScanArgs,
+    /// Whether or not it should actually be deleted
+    #[arg(long, default_value_t = false)]
+    actually_delete: bool,
+    /// Move target directories to the trash instead of deleting them permanently
+    #[arg(long, default_value_t = false, conflicts_with = "actually_delete")]
+    trash: bool,
+    /// Move target directories into quarantine, from where they can be restored until they are purged
+    #[arg(long, default_value_t = false, conflicts_with_all = ["actually_delete", "trash"])]
+    quarantine: bool,
+    /// Ask before cleaning each target directory, deleting them unless --trash or --quarantine is given
+    #[arg(short, long, default_value_t = false)]
+    interactive: bool,
+    /// Configuration file listing the roots to clean, which is read from the XDG config directory
+    /// when no path is given
+    #[arg(long, conflicts_with = "path")]
+    config: Option<PathBuf>,
+    /// Clean the roots of this profile from the configuration file instead of its top level roots
+    #[arg(long, conflicts_with = "path")]
+    profile: Option<String>,
+}
+/// Arguments deciding which target directories are found and which of them are old enough
+#[derive(clap::Args, Debug)]
+struct ScanArgs {
     /// Path of the folder to clean
     #[arg(short, long)]
-    path: PathBuf,
+    path: Option<PathBuf>,
     /// Minimum number of days since modification to be cleaned
     #[arg(short, long)]
-    days_old: usize,
-    /// Whether or not it should actually be deleted
+    days_old: Option<usize>,
+    /// Don't scan directories matching this glob, globs without a slash match directory names
+    #[arg(long, value_name = "GLOB", value_parser = parse_exclude)]
+    exclude: Vec<Pattern>,
+    /// Only look for projects of these build systems, such as cargo, instead of all of them
+    #[arg(long = "detector", value_name = "NAME", value_delimiter = ',', value_parser = parse_detector)]
+    detectors: Vec<String>,
+    /// Don't look for projects of this build system
+    #[arg(long = "no-detector", value_name = "NAME", value_parser = parse_detector)]
+    disabled_detectors: Vec<String>,
+    /// Keep target directories smaller than this, such as 100MB
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    min_size: Option<u64>,
+    /// Clean the least recently used target directories until this much space is freed, such as 20GB
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    free: Option<u64>,
+    /// Clean the least recently used target directories until their filesystems have this much
+    /// space available, such as 50GB or 20%
+    #[arg(long, value_name = "SIZE|PERCENT", value_parser = parse_free_space, conflicts_with = "free")]
+    until_free: Option<FreeSpace>,
+    /// Keep scanning inside Cargo projects for nested projects with their own target directories
     #[arg(long, default_value_t = false)]
-    actually_delete: bool,
+    nested: bool,
+    /// Which files count as activity when deciding whether a project is old enough
+    #[arg(long, value_enum, default_value_t = ActivitySource::Target)]
+    activity: ActivitySource,
+    /// Names of directories in project sources that don't count as activity
+    #[arg(long = "ignore-dir", value_name = "NAME", default_values = [".git"])]
+    ignored_dirs: Vec<OsString>,
+    /// Also count commits, reflog entries and index changes of the enclosing git repository as activity
+    #[arg(long, default_value_t = false)]
+    git_activity: bool,
+    /// How to print what the scan finds
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+    /// Count the lengths of files instead of the space they take up on disk
+    #[arg(long, default_value_t = false)]
+    apparent_size: bool,
+    /// Walk every target directory completely, reporting the size of kept ones too and how much
+    /// would be freed at other ages
+    #[arg(long, default_value_t = false)]
+    full_report: bool,
+    /// Number of threads to scan with, 0 uses one per CPU
+    #[arg(short = 'j', long, default_value_t = 0)]
+    threads: usize,
 }
-/// Returns true if target dir should be deleted
-pub fn check_target_dir_date(dir: &Path, cutoff: SystemTime) -> Option<u64> {
-    let mut total_size = 0;
-    for entry in WalkDir::new(dir) {
-        match entry {
-            Ok(entry) => {
-                let a = 0;
-                match entry.metadata() {
-                    Ok(metadata) => {
-                        match metadata.modified() {
-                            Ok(time) => {
-                                if time > cutoff {
-                                    return None;
-                                }
-                            }
-                            Err(e) => {
-                                if e.kind() == ErrorKind::Unsupported {
-                                    println!("This platform does not support finding the modification date of files!");
-                                    std::process::exit(1);
-                                }
-                            }
-                        }
-                        if metadata.is_file() {
-                            total_size += metadata.len();
-                        }
-                    }
-                    Err(e) => {
-                        let io_error = e.io_error();
-                        if io_error.is_some() && io_error.unwrap().kind() == ErrorKind::Unsupported
-                        {
-                            println!("This platform does not support finding the metadata date of files!");
-                            std::process::exit(1);
-                        }
-                        println!(
-                            "Error accessing metadata of file {}: {e}, skipping cleaning folder {}",
-                            entry.path().display(),
-                            dir.display()
-                        );
-                        return None;
-                    }
-                }
-            }
-            Err(e) => println!("Error accessing entry in folder: {e}"),
-        }
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Write the target directories that would be cleaned to a plan file, without cleaning anything
+    Plan {
+        #[command(flatten)]
+        scan: ScanArgs,
+        /// Where to write the plan
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Clean the target directories listed in a plan file, skipping any that changed since
+    Apply {
+        /// The plan file written by `plan`
+        plan: PathBuf,
+        /// Move target directories to the trash instead of deleting them permanently
+        #[arg(long, default_value_t = false)]
+        trash: bool,
+        /// Move target directories into quarantine instead of deleting them permanently
+        #[arg(long, default_value_t = false, conflicts_with = "trash")]
+        quarantine: bool,
+    },
+    /// Restore everything quarantined by the most recent cleanup
+    Undo,
+    /// Restore a single quarantined target directory to where it came from
+    Restore {
+        /// Original or quarantined path of the target directory
+        path: PathBuf,
+    },
+    /// Permanently delete quarantined target directories
+    Purge {
+        /// Only purge directories that have been quarantined for at least this many days
+        #[arg(long, value_name = "DAYS")]
+        older_than: u64,
+    },
+    /// Browse the target directories in a terminal UI as they are found, and delete or trash a
+    /// selection of them
+    Tui {
+        /// Path of the folder to scan
+        #[arg(short, long)]
+        path: PathBuf,
+        /// Only show target directories with no activity in this many days at first
+        #[arg(short, long)]
+        days_old: Option<u64>,
+        /// Don't scan directories matching this glob, globs without a slash match directory names
+        #[arg(long, value_name = "GLOB", value_parser = parse_exclude)]
+        exclude: Vec<Pattern>,
+        /// Only look for projects of these build systems, such as cargo, instead of all of them
+        #[arg(long = "detector", value_name = "NAME", value_delimiter = ',', value_parser = parse_detector)]
+        detectors: Vec<String>,
+        /// Don't look for projects of this build system
+        #[arg(long = "no-detector", value_name = "NAME", value_parser = parse_detector)]
+        disabled_detectors: Vec<String>,
+        /// Keep scanning inside Cargo projects for nested projects with their own target directories
+        #[arg(long, default_value_t = false)]
+        nested: bool,
+        /// Which files count as activity when showing the age of a project
+        #[arg(long, value_enum, default_value_t = ActivitySource::Target)]
+        activity: ActivitySource,
+        /// Names of directories in project sources that don't count as activity
+        #[arg(long = "ignore-dir", value_name = "NAME", default_values = [".git"])]
+        ignored_dirs: Vec<OsString>,
+        /// Count the lengths of files instead of the space they take up on disk
+        #[arg(long, default_value_t = false)]
+        apparent_size: bool,
+        /// Number of threads to scan with, 0 uses one per CPU
+        #[arg(short = 'j', long, default_value_t = 0)]
+        threads: usize,
+    },
+}
+/// Describes what was cleaned in each root, one line each
+fn describe_roots(roots: &[RootSummary]) -> String {
+    roots
+        .iter()
+        .map(|root| {
+            format!(
+                "\n{} ({}): {} of data in {} target folders cleaned, {} kept",
+                root.name
+                    .clone()
+                    .unwrap_or_else(|| root.path.display().to_string()),
+                root.mode,
+                humansize::format_size(root.bytes, DECIMAL),
+                root.cleaned,
+                root.kept
+            )
+        })
+        .collect()
+}
+/// Describes the files in cleaned target folders that aren't freed, as they have hardlinks elsewhere
+fn describe_linked(linked_bytes: u64) -> String {
+    if linked_bytes == 0 {
+        return String::new();
     }
-    Some(total_size)
+    format!(
+        "\n{} of files in those target folders isn't counted, as they have hardlinks elsewhere and stay on disk",
+        humansize::format_size(linked_bytes, DECIMAL)
+    )
 }
-pub fn scan_for_target_dirs(
-    dir: PathBuf,
-    cutoff: Option<SystemTime>,
-    actually_delete: bool,
-    stack: &mut Vec<PathBuf>,
-) -> u64 {
-    let mut to_check = Vec::new();
-    let mut has_cargo_toml = false;
-    let mut has_target_dir = false;
-    match read_dir(&dir) {
-        Ok(entries) => {
-            for entry in entries {
-                match entry {
-                    Ok(entry) => {
-                        if let Ok(name) = entry.file_name().into_string() {
-                            if name.as_str() == "Cargo.toml" {
-                                has_cargo_toml = true
-                            } else if name.as_str() == "target" {
-                                has_target_dir = true
-                            }
-                            if has_cargo_toml && has_target_dir {
-                                break;
-                            }
-                        }
-                        let file_type = entry.file_type().unwrap();
-                        let path = entry.path();
-                        if file_type.is_dir() {
-                            to_check.push(path);
-                        } else if file_type.is_symlink() {
-                            match read_link(&path) {
-                                Ok(inner) => {
-                                    let symlink_target = if inner.is_relative() {
-                                        dir.join(&inner)
-                                    } else {
-                                        inner
-                                    };
-                                    match std::fs::metadata(&symlink_target) {
-                                        Ok(metadata) => {
-                                            if metadata.is_dir() {
-                                                to_check.push(path);
-                                            }
-                                        }
-                                        Err(e) => println!(
-                                        "Error reading metadata of entry {} behind symlink {}: {}",
-                                        symlink_target.display(),
-                                        path.display(),
-                                        e
-                                    ),
-                                    }
-                                }
-                                Err(e) => {
-                                    println!("Error following symlink {}: {}", path.display(), e)
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => println!("Error accessing entry in folder: {e}"),
+/// Describes how much other values of `--days-old` would free, one line each
+fn describe_freed_at_age(freed_at_age: &[FreedAtAge]) -> String {
+    freed_at_age
+        .iter()
+        .map(|freed| {
+            format!(
+                "\nWith --days-old {}, {} of data in {} target folders would be freed",
+                freed.days,
+                humansize::format_size(freed.bytes, DECIMAL),
+                freed.targets
+            )
+        })
+        .collect()
+}
+/// A directory to scan, along with how to clean it
+struct ScanRoot {
+    path: PathBuf,
+    /// Name from the configuration file
+    name: Option<String>,
+    options: ScanOptions,
+}
+/// Loads the pinned projects, reporting errors and treating the pins as empty then
+fn load_pins() -> HashSet<PathBuf> {
+    interactive::load_pins().unwrap_or_else(|e| {
+        let path = interactive::pins_path().unwrap_or_default();
+        output::error(
+            &path,
+            e.kind(),
+            format!(
+                "Error reading pinned projects from {}: {}",
+                path.display(),
+                e
+            ),
+        );
+        HashSet::new()
+    })
+}
+fn parse_exclude(glob: &str) -> Result<Pattern, String> {
+    Pattern::new(glob).map_err(|e| e.to_string())
+}
+fn parse_detector(name: &str) -> Result<String, String> {
+    detector::find(name).map(|detector| detector.name().to_owned())
+}
+/// Picks the detectors to scan with, exiting with a usage error if there are none
+fn select_detectors(enable: &[String], disable: &[String]) -> Vec<&'static dyn Detector> {
+    match detector::select(enable, disable) {
+        Ok(detectors) if !detectors.is_empty() => detectors,
+        Ok(_) => Args::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "every detector is disabled, so nothing would be found",
+            )
+            .exit(),
+        Err(e) => Args::command()
+            .error(clap::error::ErrorKind::InvalidValue, e)
+            .exit(),
+    }
+}
+/// Exits with a usage error about a missing argument
+fn missing_argument(arg: &str) -> ! {
+    Args::command()
+        .error(
+            clap::error::ErrorKind::MissingRequiredArgument,
+            format!("the following required arguments were not provided:\n  {arg}"),
+        )
+        .exit()
+}
+/// Sets up the pool of `threads` threads that scans run on
+fn start_threads(threads: usize) {
+    if let Err(e) = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build_global()
+    {
+        output::note(format!("Error starting scan threads: {e}"));
+    }
+}
+/// Builds the scan options and returns them along with the root to scan
+///
+/// The root is only required when there is no configuration file to read it from
+fn scan_options(scan: ScanArgs, mode: CleanMode, allow_config: bool) -> (PathBuf, ScanOptions) {
+    output::set_format(scan.format);
+    start_threads(scan.threads);
+    let path = match scan.path {
+        Some(path) => path,
+        None if allow_config => PathBuf::new(),
+        None => missing_argument("--path <PATH>"),
+    };
+    let goal = scan
+        .free
+        .map(Goal::Free)
+        .or(scan.until_free.map(Goal::UntilFree));
+    // Roots from a configuration file can set their own age
+    if scan.days_old.is_none() && goal.is_none() && !path.as_os_str().is_empty() {
+        missing_argument("--days-old <DAYS_OLD>");
+    }
+    let started = SystemTime::now();
+    let options = ScanOptions {
+        cutoff: cutoff_for(scan.days_old.unwrap_or(0), started),
+        mode,
+        nested: scan.nested,
+        activity: scan.activity,
+        ignored_dirs: scan.ignored_dirs,
+        git_activity: scan.git_activity,
+        full_report: scan.full_report,
+        apparent_size: scan.apparent_size,
+        goal,
+        min_size: scan.min_size.unwrap_or(0),
+        policy: None,
+        excludes: scan.exclude,
+        detectors: select_detectors(&scan.detectors, &scan.disabled_detectors),
+        interactive: false,
+        pins: load_pins(),
+        started,
+    };
+    (path, options)
+}
+/// Reads the roots to scan from the configuration file, with `options` and `days_old` from the
+/// command line as the fallback for everything the file leaves out
+///
+/// `mode` is the mode given on the command line if any, which overrides that of every root
+fn config_roots(
+    config_path: Option<PathBuf>,
+    profile: Option<&str>,
+    options: &ScanOptions,
+    days_old: Option<usize>,
+    mode: Option<CleanMode>,
+) -> Result<Vec<ScanRoot>, String> {
+    let explicit = config_path.is_some() || profile.is_some();
+    let config_path = match config_path.or_else(config::default_path) {
+        Some(path) => path,
+        None => missing_argument("--path <PATH>"),
+    };
+    let config = match config::read(&config_path) {
+        Ok(config) => config,
+        // Without a configuration file the path is simply missing
+        Err(e) if e.kind() == ErrorKind::NotFound && !explicit => missing_argument("--path <PATH>"),
+        Err(e) => return Err(format!("Error reading {}: {}", config_path.display(), e)),
+    };
+    let fallback = config::Policy {
+        days_old,
+        min_size: Some(options.min_size),
+        exclude: (!options.excludes.is_empty()).then(|| {
+            options
+                .excludes
+                .iter()
+                .map(|pattern| pattern.as_str().to_owned())
+                .collect()
+        }),
+        mode: None,
+        detectors: Some(
+            options
+                .detectors
+                .iter()
+                .map(|detector| detector.name().to_owned())
+                .collect(),
+        ),
+        disable_detectors: None,
+    };
+    let base_dir = config_path.parent().unwrap_or(Path::new(""));
+    config
+        .roots(profile, base_dir)?
+        .into_iter()
+        .map(|root| {
+            let policy = root.policy.or(&fallback);
+            let days_old = match (policy.days_old, options.goal) {
+                (Some(days_old), _) => days_old,
+                (None, Some(_)) => 0,
+                (None, None) => {
+                    return Err(format!(
+                        "no days_old is set for {}, and --days-old wasn't given",
+                        root.path.display()
+                    ))
                 }
+            };
+            Ok(ScanRoot {
+                options: ScanOptions {
+                    cutoff: cutoff_for(days_old, options.started),
+                    mode: mode.or(policy.mode).unwrap_or(options.mode),
+                    min_size: policy.min_size.unwrap_or(0),
+                    excludes: policy.exclude_patterns()?,
+                    detectors: policy.detectors()?,
+                    ..options.clone()
+                },
+                path: root.path,
+                name: root.name,
+            })
+        })
+        .collect()
+}
+/// Scans `path` for target directories, handing what it reports to `print` and exiting if the
+/// root can't be read
+fn run_scan(path: PathBuf, options: &ScanOptions, state: &mut ScanState, print: fn(Record)) {
+    match dir_key(&path) {
+        Ok(key) => {
+            // Roots from a configuration file may overlap
+            if !state.visited_dirs.insert(key) {
+                output::print(Record::skip(
+                    &path,
+                    "already scanned through another path",
+                    Some(format!(
+                        "Skipping {}, as it has already been scanned through another path",
+                        path.display()
+                    )),
+                ));
+                return;
             }
         }
-        Err(e) => println!("Error scanning directory {}: {}", dir.display(), e),
+        Err(e) => {
+            output::error(
+                &path,
+                e.kind(),
+                format!("Error reading metadata of {}: {}", path.display(), e),
+            );
+            // There is nothing to scan, so end the output the way a finished scan would
+            output::finish();
+            std::process::exit(1);
+        }
     }
-    if has_cargo_toml && has_target_dir {
-        let target_path = dir.join("target");
-        let should_delete = if let Some(cutoff) = cutoff {
-            check_target_dir_date(&target_path, cutoff)
-        } else {
-            match fs_extra::dir::get_size(&target_path) {
-                Ok(size) => Some(size),
-                Err(e) => {
-                    println!("");
-                    return 0;
-                }
-            }
-        };
-        if let Some(size) = should_delete {
+    scan_for_target_dirs(path, options, state, print);
+}
+/// Prints what a scan for a plan reports, which says what would be cleaned as nothing is yet
+fn print_planned(record: Record) {
+    let (event, text) = record.into_parts();
+    let text = match &event {
+        Event::Target(target) if target.decision == Decision::Clean => {
+            text.map(|text| text.replacen("Deleting", "Would clean", 1))
+        }
+        _ => text,
+    };
+    output::print(Record::new(event, text));
+}
+fn write_plan(scan: ScanArgs, plan_path: &Path) -> io::Result<()> {
+    let (path, options) = scan_options(scan, CleanMode::DryRun, false);
+    let root = path.canonicalize().unwrap_or_else(|_| path.clone());
+    let start_time = Instant::now();
+    let mut state = ScanState::default();
+    run_scan(path, &options, &mut state, print_planned);
+    let mut summary = state.summary(&options, start_time);
+    summary.errors = output::error_count();
+    let plan = Plan {
+        created_at: unix_secs(options.started),
+        root,
+        entries: state.candidates,
+    };
+    plan.write(plan_path)?;
+    let text = format!(
+        "Wrote a plan to clean {} target directories with {} of data to {}",
+        plan.entries.len(),
+        humansize::format_size(summary.bytes, DECIMAL),
+        plan_path.display()
+    ) + &describe_linked(summary.linked_bytes)
+        + &describe_freed_at_age(&summary.freed_at_age);
+    output::summary(summary, text);
+    output::finish();
+    Ok(())
+}
+fn apply_plan(plan_path: &Path, mode: CleanMode) -> io::Result<()> {
+    let plan = Plan::read(plan_path)?;
+    let started = SystemTime::now();
+    let mut size = 0;
+    for entry in &plan.entries {
+        if let Err(reason) = plan::check_entry(entry, &plan.root) {
             println!(
-                "Deleting {} of files in target directory {}",
-                humansize::format_size(size, DECIMAL),
-                target_path.display()
+                "Skipping target directory {}: {}",
+                entry.target_path.display(),
+                reason
             );
-            if actually_delete {
-                if let Err(e) = std::fs::remove_dir_all(&target_path) {
-                    println!(
-                        "Error deleting target directory {}: {}",
-                        target_path.display(),
-                        e
-                    );
+            continue;
+        }
+        println!(
+            "Deleting {} of files in {}target directory {} of {}",
+            humansize::format_size(entry.size, DECIMAL),
+            if entry.stray { "stray " } else { "" },
+            entry.target_path.display(),
+            entry.project
+        );
+        match clean_candidate(entry, mode, started, false) {
+            Ok(()) => size += entry.size,
+            Err(e) => output::error(e.path(), e.kind(), &e),
+        }
+    }
+    println!(
+        "{} {} of data in target folders",
+        mode.past_tense(),
+        humansize::format_size(size, DECIMAL)
+    );
+    Ok(())
+}
+fn describe_restored(entry: &quarantine::Entry) {
+    println!(
+        "Restored {} of files to {}",
+        humansize::format_size(entry.size, DECIMAL),
+        entry.original_path.display()
+    );
+}
+fn run_command(command: Command) {
+    let result = match command {
+        Command::Plan { scan, output } => write_plan(scan, &output),
+        Command::Apply {
+            plan,
+            trash,
+            quarantine,
+        } => {
+            let mode = if trash {
+                CleanMode::Trash
+            } else if quarantine {
+                CleanMode::Quarantine
+            } else {
+                CleanMode::Delete
+            };
+            apply_plan(&plan, mode)
+        }
+        Command::Undo => quarantine::undo().and_then(|outcomes| {
+            if outcomes.is_empty() {
+                println!("Nothing is quarantined");
+            }
+            let mut result = Ok(());
+            for (entry, outcome) in outcomes {
+                match outcome {
+                    Ok(()) => describe_restored(&entry),
+                    Err(e) => {
+                        println!(
+                            "Error restoring {} to {}: {}",
+                            entry.quarantined_path.display(),
+                            entry.original_path.display(),
+                            e
+                        );
+                        result = Err(e);
+                    }
                 }
             }
-            return size;
-        } else {
-            return 0;
+            result
+        }),
+        Command::Restore { path } => {
+            quarantine::restore(&path).map(|entry| describe_restored(&entry))
         }
-    } else {
-        let mut total_size = 0;
-        'a: for thing in to_check {
-            let canonical_path = match thing.clone().canonicalize() {
-                Ok(v) => v,
-                Err(e) => {
-                    println!("Error resolving path {}: {}", thing.display(), e);
-                    continue 'a;
-                }
-            };
-            /*println!("Thing: {}", thing.display());
-            println!("Canonical: {}", canonical_path.display());
-            println!(
-                "Stack: {}",
-                stack
-                    .iter()
-                    .map(|s| s.to_str().unwrap())
-                    .collect::<Vec<_>>()
-                    .join(", ")
-            );*/
-            for i in 0..stack.len() {
-                if stack[i] == canonical_path {
-                    if stack.contains(&canonical_path) {
-                        println!("Warning: circular symlink reference detected:");
-                        for j in i..stack.len() {
-                            println!("\t{}", stack[j].display());
+        Command::Purge { older_than } => {
+            quarantine::purge(Duration::from_secs(3600 * 24 * older_than)).map(|outcomes| {
+                let mut size = 0;
+                for (entry, outcome) in outcomes {
+                    match outcome {
+                        Ok(()) => {
+                            println!(
+                                "Purged {} of files in {} from {}",
+                                humansize::format_size(entry.size, DECIMAL),
+                                entry.quarantined_path.display(),
+                                entry.original_path.display()
+                            );
+                            size += entry.size;
+                        }
+                        Err(e) => {
+                            println!("Error purging {}: {}", entry.quarantined_path.display(), e)
                         }
-                        println!("\t{}", canonical_path.display());
-                        continue 'a;
                     }
-                    break;
                 }
-            }
-            stack.push(canonical_path);
-            total_size += scan_for_target_dirs(thing, cutoff, actually_delete, stack);
-            stack.pop();
+                println!(
+                    "Purged {} of data from quarantine",
+                    humansize::format_size(size, DECIMAL)
+                )
+            })
+        }
+        Command::Tui {
+            path,
+            days_old,
+            exclude,
+            detectors,
+            disabled_detectors,
+            nested,
+            activity,
+            ignored_dirs,
+            apparent_size,
+            threads,
+        } => {
+            start_threads(threads);
+            let mut scanner = Scanner::new(path);
+            scanner.options = ScanOptions {
+                nested,
+                activity,
+                ignored_dirs,
+                apparent_size,
+                excludes: exclude,
+                detectors: select_detectors(&detectors, &disabled_detectors),
+                pins: load_pins(),
+                ..ScanOptions::default()
+            };
+            tui::run(scanner, days_old)
         }
-        return total_size;
+    };
+    if let Err(e) = result {
+        println!("Error: {e}");
+        std::process::exit(1);
     }
 }
 fn main() {
     let args = Args::parse();
-    let mut stack = Vec::new();
-    let cutoff = if args.days_old == 0 {
+    if let Some(command) = args.command {
+        run_command(command);
+        return;
+    }
+    let explicit_mode = if args.trash {
+        Some(CleanMode::Trash)
+    } else if args.quarantine {
+        Some(CleanMode::Quarantine)
+    } else if args.actually_delete {
+        Some(CleanMode::Delete)
+    } else {
         None
+    };
+    let mode = explicit_mode.unwrap_or(if args.interactive {
+        CleanMode::Delete
     } else {
-        Some(SystemTime::now() - std::time::Duration::from_secs((3600 * 24 * args.days_old) as u64))
+        CleanMode::DryRun
+    });
+    let days_old = args.scan.days_old;
+    let (path, mut options) = scan_options(args.scan, mode, true);
+    options.interactive = args.interactive;
+    let from_config = path.as_os_str().is_empty();
+    let roots = if from_config {
+        match config_roots(
+            args.config,
+            args.profile.as_deref(),
+            &options,
+            days_old,
+            explicit_mode,
+        ) {
+            Ok(roots) => roots,
+            Err(e) => {
+                println!("Error: {e}");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        vec![ScanRoot {
+            path,
+            name: None,
+            options: options.clone(),
+        }]
     };
-    println!("WARNING: recursive symlinks WILL cause this program to freeze.");
-    if !args.actually_delete {
-        println!("Because you ran without --actually-delete, no folders will actually be deleted. This will simply list out what would be deleted, which is useful for debug purposes.");
+    if !from_config && mode == CleanMode::DryRun {
+        output::note("Because you ran without --actually-delete, --trash or --quarantine, no folders will actually be deleted. This will simply list out what would be deleted, which is useful for debug purposes.");
     }
-    stack.push(args.path.clone());
     let start_time = Instant::now();
-    let size = scan_for_target_dirs(args.path, cutoff, args.actually_delete, &mut stack);
-    println!(
-        "Deleted {} of data in target folders in {} seconds",
-        humansize::format_size(size, DECIMAL),
-        (Instant::now() - start_time).as_secs_f32(),
+    let mut state = ScanState::default();
+    let mut root_summaries = Vec::new();
+    let root_modes: Vec<_> = roots.iter().map(|root| root.options.mode).collect();
+    for root in roots {
+        if from_config && root.options.mode == CleanMode::DryRun {
+            output::note(format!(
+                "{} is a dry run, so nothing in it will actually be deleted",
+                root.path.display()
+            ));
+        }
+        let cleaned_before = state.candidates.len();
+        let kept_before = state.kept;
+        run_scan(root.path.clone(), &root.options, &mut state, output::print);
+        root_summaries.push(RootSummary {
+            path: root.path,
+            name: root.name,
+            mode: root.options.mode.name(),
+            cleaned: state.candidates.len() - cleaned_before,
+            kept: state.kept - kept_before,
+            bytes: state.candidates[cleaned_before..]
+                .iter()
+                .map(|c| root.options.size_of(c.size, c.apparent_size))
+                .sum(),
+        });
+    }
+    let mut summary = state.summary(&options, start_time);
+    // Errors outside of the scans count too, such as reading the pinned projects
+    summary.errors = output::error_count();
+    let mut past_tense = mode.past_tense();
+    if from_config {
+        // Each root has its own mode
+        summary.bytes =
+            root_summaries.iter().map(|root| root.bytes).sum::<u64>() - summary.linked_bytes;
+        let mut modes = root_modes.iter();
+        if let Some(&first) = modes.next() {
+            if modes.all(|&mode| mode == first) {
+                summary.mode = first.name();
+                past_tense = first.past_tense();
+            } else {
+                summary.mode = "mixed";
+                past_tense = "Cleaned";
+            }
+        }
+        summary.roots = root_summaries;
+    }
+    let text = format!(
+        "{} {} of data in target folders ({} on disk, {} apparent size) in {} seconds{}{}{}{}",
+        past_tense,
+        humansize::format_size(summary.bytes, DECIMAL),
+        humansize::format_size(summary.disk_bytes, DECIMAL),
+        humansize::format_size(summary.apparent_bytes, DECIMAL),
+        summary.seconds,
+        describe_linked(summary.linked_bytes),
+        if summary.protected > 0 {
+            format!(
+                "\nProtected {} target folders of projects that opted out",
+                summary.protected
+            )
+        } else {
+            String::new()
+        },
+        describe_roots(&summary.roots),
+        describe_freed_at_age(&summary.freed_at_age)
     );
+    output::summary(summary, text);
+    output::finish();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    const CONFIG: &str = r#"
+days_old = 30
+mode = "trash"
+
+[[root]]
+path = "code"
+
+[[root]]
+path = "scratch"
+mode = "quarantine"
+days_old = 7
+
+[profile.ci]
+days_old = 3
+
+[[profile.ci.root]]
+path = "builds"
+"#;
+
+    fn started() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000)
+    }
+
+    /// Reads the roots of a configuration file with what was given on the command line, returning
+    /// each root's mode and cutoff
+    fn roots(
+        contents: &str,
+        profile: Option<&str>,
+        days_old: Option<usize>,
+        mode: Option<CleanMode>,
+    ) -> Result<Vec<(CleanMode, Option<SystemTime>)>, String> {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, contents).unwrap();
+        let options = ScanOptions {
+            mode: mode.unwrap_or(CleanMode::DryRun),
+            started: started(),
+            ..Default::default()
+        };
+        let roots = config_roots(Some(config_path), profile, &options, days_old, mode)?;
+        Ok(roots
+            .into_iter()
+            .map(|root| (root.options.mode, root.options.cutoff))
+            .collect())
+    }
+
+    #[test]
+    fn lets_the_configuration_file_set_what_the_command_line_leaves_out() {
+        assert_eq!(
+            roots(CONFIG, None, Some(90), None).unwrap(),
+            [
+                (CleanMode::Trash, cutoff_for(30, started())),
+                (CleanMode::Quarantine, cutoff_for(7, started())),
+            ]
+        );
+        // Profiles fall back to the top of the file
+        assert_eq!(
+            roots(CONFIG, Some("ci"), None, None).unwrap(),
+            [(CleanMode::Trash, cutoff_for(3, started()))]
+        );
+    }
+
+    #[test]
+    fn applies_explicit_modes_to_every_root() {
+        for mode in [CleanMode::Delete, CleanMode::Trash, CleanMode::Quarantine] {
+            for profile in [None, Some("ci")] {
+                let modes = roots(CONFIG, profile, None, Some(mode)).unwrap();
+                assert!(modes.iter().all(|(root_mode, _)| *root_mode == mode));
+            }
+        }
+        // Without a mode anywhere, nothing is deleted
+        let config = "days_old = 3\n[[root]]\npath = \"code\"\n";
+        assert_eq!(
+            roots(config, None, None, None).unwrap()[0].0,
+            CleanMode::DryRun
+        );
+    }
+
+    #[test]
+    fn falls_back_to_the_command_line_for_days_old() {
+        let config = "[[root]]\npath = \"code\"\n";
+        assert_eq!(
+            roots(config, None, Some(10), None).unwrap(),
+            [(CleanMode::DryRun, cutoff_for(10, started()))]
+        );
+        let error = roots(config, None, None, None).unwrap_err();
+        assert!(error.starts_with("no days_old is set for "), "{error}");
+    }
 }
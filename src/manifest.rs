@@ -0,0 +1,358 @@
+use glob::Pattern;
+use std::fs::read_to_string;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::report;
+
+/// The role a project plays, named after Cargo's terms which other build systems share
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProjectKind {
+    /// A package that isn't part of any workspace
+    Package,
+    /// The root of a workspace, which may also be a package itself
+    Workspace,
+    /// A package belonging to the workspace rooted at the given directory
+    Member(PathBuf),
+}
+
+/// A project found by one of the detectors
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub dir: PathBuf,
+    /// The package name, or the directory name for virtual workspaces and unreadable manifests
+    pub name: String,
+    pub kind: ProjectKind,
+    /// Settings from the `metadata.cleaner` table of the Cargo manifest or of its workspace
+    pub settings: Option<CleanerSettings>,
+}
+impl Project {
+    /// Returns a short human readable description such as "package foo"
+    pub fn describe(&self) -> String {
+        match self.kind {
+            ProjectKind::Package => format!("package {}", self.name),
+            ProjectKind::Workspace => format!("workspace {}", self.name),
+            ProjectKind::Member(_) => format!("workspace member {}", self.name),
+        }
+    }
+}
+
+/// Settings from a `[package.metadata.cleaner]` or `[workspace.metadata.cleaner]` table
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CleanerSettings {
+    /// Never clean the target directory
+    pub keep: bool,
+    /// Replaces `--days-old` for this project
+    pub days_old: Option<usize>,
+    /// Where the settings were found, such as "package.metadata.cleaner in /foo/Cargo.toml"
+    pub source: String,
+}
+
+/// Reads the manifest in `dir` and works out what kind of project it is
+///
+/// Unreadable manifests are reported and treated as standalone packages
+pub fn read_project(dir: &Path) -> Project {
+    let manifest = read_manifest(dir);
+    let name = manifest
+        .as_ref()
+        .and_then(|manifest| manifest.get("package")?.get("name")?.as_str())
+        .map(str::to_owned)
+        .unwrap_or_else(|| dir_name(dir));
+    let kind = match &manifest {
+        Some(manifest) if manifest.contains_key("workspace") => ProjectKind::Workspace,
+        Some(manifest) => match find_workspace_root(dir, manifest) {
+            Some(root) => ProjectKind::Member(root),
+            None => ProjectKind::Package,
+        },
+        None => ProjectKind::Package,
+    };
+    // Packages inherit the settings of their workspace unless they have their own
+    let settings = manifest
+        .as_ref()
+        .and_then(|manifest| cleaner_settings(dir, manifest))
+        .or_else(|| match &kind {
+            ProjectKind::Member(root) => cleaner_settings(root, &read_manifest(root)?),
+            _ => None,
+        });
+    Project {
+        dir: dir.to_path_buf(),
+        name,
+        kind,
+        settings,
+    }
+}
+
+/// Reads the `metadata.cleaner` table of the manifest in `dir`, preferring the package's
+fn cleaner_settings(dir: &Path, manifest: &toml::Table) -> Option<CleanerSettings> {
+    let (section, table) = ["package", "workspace"].into_iter().find_map(|section| {
+        let table = manifest.get(section)?.get("metadata")?.get("cleaner")?;
+        Some((section, table))
+    })?;
+    let source = format!(
+        "{section}.metadata.cleaner in {}",
+        dir.join("Cargo.toml").display()
+    );
+    let days_old = table
+        .get("days-old")
+        .or_else(|| table.get("days_old"))
+        .and_then(|value| match value.as_integer() {
+            Some(days) if days >= 0 => Some(days as usize),
+            _ => {
+                report::error(
+                    &dir.join("Cargo.toml"),
+                    ErrorKind::InvalidData,
+                    format!("Error in {source}: days-old must be a whole number of days"),
+                );
+                None
+            }
+        });
+    Some(CleanerSettings {
+        keep: table.get("keep").and_then(|value| value.as_bool()) == Some(true),
+        days_old,
+        source,
+    })
+}
+
+/// Lists the member directories of the workspace rooted at `root`
+pub fn workspace_members(root: &Path) -> Vec<PathBuf> {
+    let Some(manifest) = read_manifest(root) else {
+        return Vec::new();
+    };
+    let Some(workspace) = manifest.get("workspace") else {
+        return Vec::new();
+    };
+    let mut members = Vec::new();
+    for pattern in string_array(workspace, "members") {
+        let full_pattern = root.join(pattern);
+        let paths = match glob::glob(&full_pattern.to_string_lossy()) {
+            Ok(v) => v,
+            Err(e) => {
+                report::error(
+                    root,
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "Error in workspace member pattern {} of {}: {}",
+                        pattern,
+                        root.display(),
+                        e
+                    ),
+                );
+                continue;
+            }
+        };
+        for path in paths.flatten() {
+            if path.join("Cargo.toml").is_file()
+                && !is_excluded(root, workspace, &path)
+                && !members.contains(&path)
+            {
+                members.push(path);
+            }
+        }
+    }
+    members
+}
+
+/// Finds the workspace a package belongs to, the same way cargo does
+fn find_workspace_root(dir: &Path, manifest: &toml::Table) -> Option<PathBuf> {
+    // An explicit `package.workspace` key skips the search
+    if let Some(root) = manifest
+        .get("package")
+        .and_then(|package| package.get("workspace")?.as_str())
+    {
+        let root = dir.join(root);
+        return Some(root.canonicalize().unwrap_or(root));
+    }
+    for ancestor in dir.ancestors().skip(1) {
+        if !ancestor.join("Cargo.toml").is_file() {
+            continue;
+        }
+        let Some(root_manifest) = read_manifest(ancestor) else {
+            continue;
+        };
+        // Cargo stops at the first workspace above the package, whether or not it is a member
+        if let Some(workspace) = root_manifest.get("workspace") {
+            return is_member(ancestor, workspace, dir).then(|| ancestor.to_path_buf());
+        }
+    }
+    None
+}
+
+/// Returns whether `dir` is matched by the `members` globs and not by `exclude`
+fn is_member(root: &Path, workspace: &toml::Value, dir: &Path) -> bool {
+    let Ok(relative) = dir.strip_prefix(root) else {
+        return false;
+    };
+    let included = string_array(workspace, "members").any(|pattern| {
+        Pattern::new(pattern)
+            .map(|pattern| pattern.matches_path(relative))
+            .unwrap_or(false)
+    });
+    included && !is_excluded(root, workspace, dir)
+}
+
+/// Returns whether `dir` is inside one of the `exclude` paths of a workspace
+fn is_excluded(root: &Path, workspace: &toml::Value, dir: &Path) -> bool {
+    string_array(workspace, "exclude").any(|excluded| dir.starts_with(root.join(excluded)))
+}
+
+/// Iterates over the strings in an array of `table`, ignoring anything malformed
+fn string_array<'a>(table: &'a toml::Value, key: &str) -> impl Iterator<Item = &'a str> {
+    table
+        .get(key)
+        .and_then(|value| value.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|value| value.as_str())
+}
+
+fn read_manifest(dir: &Path) -> Option<toml::Table> {
+    let manifest_path = dir.join("Cargo.toml");
+    let contents = match read_to_string(&manifest_path) {
+        Ok(v) => v,
+        Err(e) => {
+            report::error(
+                &manifest_path,
+                e.kind(),
+                format!("Error reading manifest {}: {}", manifest_path.display(), e),
+            );
+            return None;
+        }
+    };
+    match contents.parse::<toml::Table>() {
+        Ok(v) => Some(v),
+        Err(e) => {
+            report::error(
+                &manifest_path,
+                ErrorKind::InvalidData,
+                format!("Error parsing manifest {}: {}", manifest_path.display(), e),
+            );
+            None
+        }
+    }
+}
+
+pub fn dir_name(dir: &Path) -> String {
+    dir.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| dir.display().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::tree;
+
+    fn package(name: &str) -> String {
+        format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\n")
+    }
+
+    /// A workspace with members matched by a glob, one of them excluded, and a package inside it
+    /// that isn't a member
+    fn workspace() -> (tempfile::TempDir, PathBuf) {
+        let dir = tree(&[
+            (
+                "w/Cargo.toml",
+                "[workspace]\nmembers = [\"crates/*\", \"tool\", \"crates/a\"]\nexclude = [\"crates/old\"]\n\n[workspace.metadata.cleaner]\ndays-old = 60\n",
+            ),
+            ("w/crates/a/Cargo.toml", &package("a")),
+            ("w/crates/b/Cargo.toml", &package("b")),
+            ("w/crates/notes/", ""),
+            ("w/crates/old/Cargo.toml", &package("old")),
+            ("w/tool/Cargo.toml", &package("tool")),
+            ("w/examples/demo/Cargo.toml", &package("demo")),
+        ]);
+        let root = dir.path().canonicalize().unwrap().join("w");
+        (dir, root)
+    }
+
+    #[test]
+    fn lists_workspace_members() {
+        let (_dir, root) = workspace();
+        // Directories without a manifest, excluded ones and duplicates are left out
+        assert_eq!(
+            workspace_members(&root),
+            [
+                root.join("crates/a"),
+                root.join("crates/b"),
+                root.join("tool")
+            ]
+        );
+        assert!(workspace_members(&root.join("tool")).is_empty());
+    }
+
+    #[test]
+    fn tells_members_from_packages() {
+        let (_dir, root) = workspace();
+        let workspace = read_project(&root);
+        assert_eq!(workspace.kind, ProjectKind::Workspace);
+        assert_eq!(workspace.name, "w");
+        let member = read_project(&root.join("crates/b"));
+        assert_eq!(member.kind, ProjectKind::Member(root.clone()));
+        assert_eq!(member.name, "b");
+        // Cargo stops at the first workspace, which these aren't part of
+        assert_eq!(
+            read_project(&root.join("crates/old")).kind,
+            ProjectKind::Package
+        );
+        assert_eq!(
+            read_project(&root.join("examples/demo")).kind,
+            ProjectKind::Package
+        );
+    }
+
+    #[test]
+    fn follows_explicit_workspace_keys() {
+        let (_dir, root) = workspace();
+        let demo = root.join("examples/demo/Cargo.toml");
+        std::fs::write(&demo, package("demo") + "workspace = \"../..\"\n").unwrap();
+        assert_eq!(
+            read_project(&root.join("examples/demo")).kind,
+            ProjectKind::Member(root)
+        );
+    }
+
+    #[test]
+    fn inherits_the_settings_of_the_workspace() {
+        let (_dir, root) = workspace();
+        let settings = read_project(&root.join("crates/a")).settings.unwrap();
+        assert_eq!(settings.days_old, Some(60));
+        assert!(!settings.keep);
+        assert_eq!(
+            settings.source,
+            format!(
+                "workspace.metadata.cleaner in {}",
+                root.join("Cargo.toml").display()
+            )
+        );
+        // Settings of the package itself replace those of the workspace
+        std::fs::write(
+            root.join("tool/Cargo.toml"),
+            package("tool") + "\n[package.metadata.cleaner]\nkeep = true\n",
+        )
+        .unwrap();
+        let settings = read_project(&root.join("tool")).settings.unwrap();
+        assert_eq!(settings.days_old, None);
+        assert!(settings.keep);
+        assert_eq!(read_project(&root.join("crates/old")).settings, None);
+    }
+
+    #[test]
+    fn reports_unreadable_manifests() {
+        let dir = tree(&[
+            ("broken/Cargo.toml", "[package"),
+            (
+                "days/Cargo.toml",
+                "[package]\nname = \"days\"\n\n[package.metadata.cleaner]\ndays-old = \"soon\"\n",
+            ),
+        ]);
+        let (broken, records) = report::capture(|| read_project(&dir.path().join("broken")));
+        assert_eq!(broken.kind, ProjectKind::Package);
+        assert_eq!(broken.name, "broken");
+        assert_eq!(records.len(), 1);
+        assert!(records[0].is_error());
+        let (days, records) = report::capture(|| read_project(&dir.path().join("days")));
+        assert_eq!(days.settings.unwrap().days_old, None);
+        assert_eq!(records.len(), 1);
+        assert!(records[0].is_error());
+    }
+}
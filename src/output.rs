@@ -0,0 +1,102 @@
+//! Printing what scans report, either as the usual text or as JSON for scripts
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fmt::Display;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use code_workspaces_cleaner_upper::report::{
+    ErrorEvent, Event, Record, SkipEvent, Summary, TargetEvent,
+};
+
+/// How scan results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable lines
+    Text,
+    /// A single JSON document once the scan has finished
+    Json,
+    /// One JSON object per line as things happen
+    Jsonl,
+}
+
+/// Everything a scan reported, printed at the end in the `json` format
+#[derive(Debug, Default, Serialize)]
+struct Document {
+    targets: Vec<TargetEvent>,
+    errors: Vec<ErrorEvent>,
+    skipped: Vec<SkipEvent>,
+    summary: Option<Summary>,
+}
+
+static FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+static DOCUMENT: Mutex<Option<Document>> = Mutex::new(None);
+static ERROR_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets the output format, which is text until this is called
+pub fn set_format(format: OutputFormat) {
+    let _ = FORMAT.set(format);
+}
+
+pub fn format() -> OutputFormat {
+    FORMAT.get().copied().unwrap_or(OutputFormat::Text)
+}
+
+/// Returns how many errors have been printed so far
+pub fn error_count() -> usize {
+    ERROR_COUNT.load(Ordering::Relaxed)
+}
+
+/// Prints an error involving `path`, `message` is the complete human readable description
+pub fn error(path: &Path, kind: ErrorKind, message: impl Display) {
+    print(Record::error(path, kind, message));
+}
+
+/// Prints the totals of a finished scan
+pub fn summary(summary: Summary, text: String) {
+    print(Record::new(Event::Summary(summary), Some(text)));
+}
+
+/// Prints a message that only makes sense to humans, so it is left out of JSON output
+pub fn note(text: impl Display) {
+    if format() == OutputFormat::Text {
+        println!("{text}");
+    }
+}
+
+/// Prints something a scan reported, or holds it back for the document in the `json` format
+pub fn print(record: Record) {
+    if record.is_error() {
+        ERROR_COUNT.fetch_add(1, Ordering::Relaxed);
+    }
+    let (event, text) = record.into_parts();
+    match format() {
+        OutputFormat::Text => {
+            if let Some(text) = text {
+                println!("{text}");
+            }
+        }
+        OutputFormat::Jsonl => println!("{}", serde_json::to_string(&event).unwrap()),
+        OutputFormat::Json => {
+            let mut document = DOCUMENT.lock().unwrap();
+            let document = document.get_or_insert_with(Document::default);
+            match event {
+                Event::Target(event) => document.targets.push(event),
+                Event::Error(event) => document.errors.push(event),
+                Event::Skip(event) => document.skipped.push(event),
+                Event::Summary(summary) => document.summary = Some(summary),
+            }
+        }
+    }
+}
+
+/// Prints the collected document in the `json` format, does nothing otherwise
+pub fn finish() {
+    if format() != OutputFormat::Json {
+        return;
+    }
+    let document = DOCUMENT.lock().unwrap().take().unwrap_or_default();
+    println!("{}", serde_json::to_string_pretty(&document).unwrap());
+}
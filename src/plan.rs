@@ -0,0 +1,197 @@
+//! Plan files, which record the target directories a scan would clean so that they can be
+//! reviewed before `apply` cleans them
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::detector::{self, Listing};
+use crate::report;
+use crate::{check_target_dir_date, serde_path, subsec_nanos, unix_secs, Candidate, TargetCheck};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Plan {
+    /// Unix time the plan was made at
+    pub created_at: u64,
+    /// The directory that was scanned
+    #[serde(with = "serde_path")]
+    pub root: PathBuf,
+    pub entries: Vec<Candidate>,
+}
+impl Plan {
+    pub fn read(path: &Path) -> io::Result<Plan> {
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, serde_json::to_vec_pretty(self)?)
+    }
+}
+
+/// Checks that a planned target directory is still safe to clean, returning why not otherwise
+///
+/// It has to still exist, the detector that found it has to still find it when looking at its
+/// project again, and nothing in it may have been modified since the plan was made. `root` is the
+/// directory that was scanned
+pub fn check_entry(entry: &Candidate, root: &Path) -> Result<(), String> {
+    match fs::symlink_metadata(&entry.target_path) {
+        Ok(metadata) if metadata.is_dir() => (),
+        Ok(_) => return Err("it is no longer a directory".to_owned()),
+        Err(e) => return Err(format!("it can no longer be read: {e}")),
+    }
+    let detector = detector::find(&entry.kind)?;
+    // Members are found from their workspace, build directories of their own, and caches spread
+    // through the sources from the directory they are in
+    let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let dirs = entry
+        .project_dir
+        .ancestors()
+        .take_while(|dir| dir.starts_with(&root))
+        .chain(entry.target_path.parent())
+        .chain([entry.target_path.as_path()]);
+    // What detectors report was reported when the plan was made
+    let (found, _) = report::capture(|| {
+        dirs.filter_map(|dir| Some((dir, Listing::read(dir).ok()?)))
+            .flat_map(|(dir, listing)| detector.detect(dir, &listing))
+            .flat_map(|detection| detection.artifacts)
+            .any(|artifact| {
+                artifact.path == entry.target_path
+                    || artifact.path.canonicalize().ok().as_ref() == Some(&entry.target_path)
+            })
+    });
+    if !found {
+        return Err(format!(
+            "it is no longer a target directory of {}",
+            entry.project
+        ));
+    }
+    let TargetCheck::Old(stats, _) = check_target_dir_date(&entry.target_path, None) else {
+        return Err("it could no longer be fully read".to_owned());
+    };
+    let newest = stats
+        .newest
+        .map(|time| (unix_secs(time), subsec_nanos(time)));
+    let planned = entry
+        .newest_modified
+        .map(|secs| (secs, entry.newest_modified_nanos));
+    if newest > planned {
+        return Err("it was modified after the plan was made".to_owned());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{set_modified, tree};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    /// When the fixture was last modified, with a fraction of a second
+    fn planned_time() -> SystemTime {
+        UNIX_EPOCH + Duration::new(1_700_000_000, 100)
+    }
+
+    /// Makes a Cargo project last modified at [`planned_time`], along with a plan entry for its
+    /// target directory
+    fn project() -> (tempfile::TempDir, PathBuf, Candidate) {
+        let dir = tree(&[
+            (
+                "p/Cargo.toml",
+                "[package]\nname = \"p\"\nversion = \"0.1.0\"\n",
+            ),
+            ("p/src/lib.rs", ""),
+            ("p/target/debug/output", "output"),
+        ]);
+        set_modified(dir.path(), planned_time());
+        let root = dir.path().canonicalize().unwrap();
+        let entry = Candidate {
+            target_path: root.join("p/target"),
+            project_dir: root.join("p"),
+            project: "package p".to_owned(),
+            stray: false,
+            size: 0,
+            apparent_size: 0,
+            newest_modified: Some(unix_secs(planned_time())),
+            newest_modified_nanos: subsec_nanos(planned_time()),
+            last_activity: None,
+            kind: "cargo".to_owned(),
+            reasons: Vec::new(),
+            hardlinks: Vec::new(),
+        };
+        (dir, root, entry)
+    }
+
+    #[test]
+    fn accepts_unchanged_entries() {
+        let (_dir, root, entry) = project();
+        assert_eq!(check_entry(&entry, &root), Ok(()));
+    }
+
+    #[test]
+    fn rejects_missing_target_directories() {
+        let (_dir, root, entry) = project();
+        fs::remove_dir_all(&entry.target_path).unwrap();
+        let reason = check_entry(&entry, &root).unwrap_err();
+        assert!(reason.starts_with("it can no longer be read: "), "{reason}");
+        fs::write(&entry.target_path, "").unwrap();
+        assert_eq!(
+            check_entry(&entry, &root),
+            Err("it is no longer a directory".to_owned())
+        );
+    }
+
+    #[test]
+    fn rejects_directories_that_are_no_longer_targets() {
+        let (_dir, root, entry) = project();
+        // Only the detector that found it counts
+        let node_entry = Candidate {
+            kind: "node".to_owned(),
+            ..entry.clone()
+        };
+        assert_eq!(
+            check_entry(&node_entry, &root),
+            Err("it is no longer a target directory of package p".to_owned())
+        );
+        fs::remove_file(root.join("p/Cargo.toml")).unwrap();
+        assert_eq!(
+            check_entry(&entry, &root),
+            Err("it is no longer a target directory of package p".to_owned())
+        );
+    }
+
+    #[test]
+    fn rejects_modified_target_directories() {
+        let (_dir, root, entry) = project();
+        // Even within the same second as the plan was made
+        let output = entry.target_path.join("debug/output");
+        set_modified(&output, planned_time() + Duration::from_nanos(100));
+        assert_eq!(
+            check_entry(&entry, &root),
+            Err("it was modified after the plan was made".to_owned())
+        );
+        set_modified(&output, planned_time());
+        fs::write(entry.target_path.join("new"), "").unwrap();
+        assert_eq!(
+            check_entry(&entry, &root),
+            Err("it was modified after the plan was made".to_owned())
+        );
+    }
+
+    #[test]
+    fn accepts_caches_spread_through_the_sources() {
+        let dir = tree(&[
+            ("py/pyproject.toml", ""),
+            ("py/app/__pycache__/mod.pyc", ""),
+        ]);
+        set_modified(dir.path(), planned_time());
+        let root = dir.path().canonicalize().unwrap();
+        let (_, _, entry) = project();
+        let entry = Candidate {
+            target_path: root.join("py/app/__pycache__"),
+            project_dir: root.join("py"),
+            project: "package py".to_owned(),
+            kind: "python".to_owned(),
+            ..entry
+        };
+        assert_eq!(check_entry(&entry, &root), Ok(()));
+    }
+}
@@ -0,0 +1,118 @@
+//! Deciding which of the target directories a scan found are cleaned
+//!
+//! Scans judge every target directory with [`OlderThan`] and [`MinSize`] as the options say,
+//! along with [`ScanOptions::policy`](crate::ScanOptions::policy) if it is set
+use humansize::DECIMAL;
+use std::fmt;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::{unix_secs, Candidate};
+
+/// What was decided about a target directory, along with why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Judgement {
+    Clean(String),
+    Keep(String),
+}
+
+/// Decides whether target directories are cleaned or kept
+pub trait Policy {
+    fn judge(&self, candidate: &Candidate) -> Judgement;
+}
+impl<F: Fn(&Candidate) -> Judgement> Policy for F {
+    fn judge(&self, candidate: &Candidate) -> Judgement {
+        self(candidate)
+    }
+}
+/// Keeps a target directory if any of the policies keeps it
+impl Policy for [Box<dyn Policy + '_>] {
+    fn judge(&self, candidate: &Candidate) -> Judgement {
+        let mut reasons = Vec::new();
+        for policy in self {
+            match policy.judge(candidate) {
+                Judgement::Clean(reason) => reasons.push(reason),
+                keep => return keep,
+            }
+        }
+        Judgement::Clean(reasons.join(", "))
+    }
+}
+
+/// Cleans target directories whose projects had no activity in a number of days
+#[derive(Debug, Clone, Copy)]
+pub struct OlderThan {
+    pub days: u64,
+    /// Unix time activity has to be after to keep a target directory
+    pub cutoff: u64,
+}
+impl OlderThan {
+    /// Measures ages from now
+    pub fn new(days: u64) -> Self {
+        let now = unix_secs(SystemTime::now());
+        OlderThan {
+            days,
+            cutoff: now.saturating_sub(days * 3600 * 24),
+        }
+    }
+    /// Returns whether activity at `last_activity`, a unix time, is recent enough to keep a
+    /// target directory
+    pub fn is_recent(&self, last_activity: Option<u64>) -> bool {
+        last_activity.is_some_and(|time| time > self.cutoff)
+    }
+}
+impl Policy for OlderThan {
+    fn judge(&self, candidate: &Candidate) -> Judgement {
+        if self.is_recent(candidate.last_activity) {
+            Judgement::Keep(format!("there was activity in the last {} days", self.days))
+        } else {
+            Judgement::Clean(format!(
+                "there was no activity in the last {} days",
+                self.days
+            ))
+        }
+    }
+}
+
+/// Keeps target directories that are smaller than a number of bytes
+#[derive(Debug, Clone, Copy)]
+pub struct MinSize {
+    pub bytes: u64,
+    /// Whether the apparent size is compared instead of the space used on disk
+    pub apparent_size: bool,
+}
+impl Policy for MinSize {
+    fn judge(&self, candidate: &Candidate) -> Judgement {
+        let min_size = humansize::format_size(self.bytes, DECIMAL);
+        let size = if self.apparent_size {
+            candidate.apparent_size
+        } else {
+            candidate.size
+        };
+        if size < self.bytes {
+            Judgement::Keep(format!("it is smaller than the minimum size of {min_size}"))
+        } else {
+            Judgement::Clean(format!("it is at least the minimum size of {min_size}"))
+        }
+    }
+}
+
+/// A policy that can be shared by the threads of a scan, as set in
+/// [`ScanOptions::policy`](crate::ScanOptions::policy)
+#[derive(Clone)]
+pub struct SharedPolicy(pub Arc<dyn Policy + Send + Sync>);
+impl SharedPolicy {
+    pub fn new(policy: impl Policy + Send + Sync + 'static) -> Self {
+        SharedPolicy(Arc::new(policy))
+    }
+}
+impl Policy for SharedPolicy {
+    fn judge(&self, candidate: &Candidate) -> Judgement {
+        self.0.judge(candidate)
+    }
+}
+impl fmt::Debug for SharedPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedPolicy(..)")
+    }
+}
@@ -0,0 +1,346 @@
+//! A grace period before cleanups become permanent
+//!
+//! Quarantined target directories are renamed into a quarantine directory on the same filesystem
+//! and recorded in a manifest, so that they can be put back with `undo` or `restore` until they
+//! are deleted for real with `purge`
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::xdg::{state_dir, APP_NAME};
+use crate::{serde_path, unix_secs};
+
+/// A quarantined target directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// Where the directory was before it was quarantined
+    #[serde(with = "serde_path")]
+    pub original_path: PathBuf,
+    /// Where the directory is now
+    #[serde(with = "serde_path")]
+    pub quarantined_path: PathBuf,
+    pub size: u64,
+    /// Unix time the directory was quarantined at
+    pub quarantined_at: u64,
+    /// Unix time the cleanup started at, which is shared by everything quarantined in one run
+    pub run: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    entries: Vec<Entry>,
+}
+
+/// Moves the directory at `path` into quarantine and records it in the manifest
+pub fn quarantine(path: &Path, size: u64, run: SystemTime) -> io::Result<PathBuf> {
+    quarantine_in(&state_dir()?, path, size, run)
+}
+
+/// Quarantines as [`quarantine`] does, with `state` as the state directory
+fn quarantine_in(state: &Path, path: &Path, size: u64, run: SystemTime) -> io::Result<PathBuf> {
+    let path = path.canonicalize()?;
+    let quarantine_dir = quarantine_dir_for(state, &path)?;
+    fs::create_dir_all(&quarantine_dir)?;
+    let now = unix_secs(SystemTime::now());
+    // Name entries after the project so that the quarantine directory is easy to browse by hand
+    let project_name = path
+        .parent()
+        .and_then(|parent| parent.file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut quarantined_path = quarantine_dir.join(format!("{now}-{project_name}"));
+    let mut i = 1;
+    while quarantined_path.exists() {
+        i += 1;
+        quarantined_path = quarantine_dir.join(format!("{now}-{project_name}.{i}"));
+    }
+    fs::rename(&path, &quarantined_path)?;
+    let mut manifest = load_manifest(state)?;
+    manifest.entries.push(Entry {
+        original_path: path.clone(),
+        quarantined_path: quarantined_path.clone(),
+        size,
+        quarantined_at: now,
+        run: unix_secs(run),
+    });
+    if let Err(e) = save_manifest(state, &manifest) {
+        // An unrecorded directory could never be restored, so put it back
+        let _ = fs::rename(&quarantined_path, &path);
+        return Err(e);
+    }
+    Ok(quarantined_path)
+}
+
+/// Restores everything quarantined by the most recent run, returning each entry along with
+/// whether it was restored, which is nothing if nothing is quarantined
+pub fn undo() -> io::Result<Vec<(Entry, io::Result<()>)>> {
+    undo_in(&state_dir()?)
+}
+
+fn undo_in(state: &Path) -> io::Result<Vec<(Entry, io::Result<()>)>> {
+    let mut manifest = load_manifest(state)?;
+    let Some(last_run) = manifest.entries.iter().map(|entry| entry.run).max() else {
+        return Ok(Vec::new());
+    };
+    let mut outcomes = Vec::new();
+    manifest.entries.retain(|entry| {
+        if entry.run != last_run {
+            return true;
+        }
+        let result = restore_entry(entry);
+        let failed = result.is_err();
+        outcomes.push((entry.clone(), result));
+        failed
+    });
+    save_manifest(state, &manifest)?;
+    Ok(outcomes)
+}
+
+/// Restores the quarantined directory that came from `path`, returning its entry
+pub fn restore(path: &Path) -> io::Result<Entry> {
+    restore_in(&state_dir()?, path)
+}
+
+fn restore_in(state: &Path, path: &Path) -> io::Result<Entry> {
+    let mut manifest = load_manifest(state)?;
+    // The original location may be gone, so compare against its canonical form when possible
+    let path = path.canonicalize().unwrap_or_else(|_| absolute(path));
+    let Some(index) = manifest
+        .entries
+        .iter()
+        .rposition(|entry| entry.original_path == path || entry.quarantined_path == path)
+    else {
+        return Err(io::Error::new(
+            ErrorKind::NotFound,
+            format!("{} is not quarantined", path.display()),
+        ));
+    };
+    restore_entry(&manifest.entries[index])?;
+    let entry = manifest.entries.remove(index);
+    save_manifest(state, &manifest)?;
+    Ok(entry)
+}
+
+/// Permanently deletes quarantined directories that have been quarantined for longer than
+/// `older_than`, returning each entry along with whether it was deleted
+///
+/// Entries whose directory was already deleted by hand are forgotten without being returned
+pub fn purge(older_than: Duration) -> io::Result<Vec<(Entry, io::Result<()>)>> {
+    purge_in(&state_dir()?, older_than)
+}
+
+fn purge_in(state: &Path, older_than: Duration) -> io::Result<Vec<(Entry, io::Result<()>)>> {
+    let mut manifest = load_manifest(state)?;
+    let cutoff = unix_secs(
+        SystemTime::now()
+            .checked_sub(older_than)
+            .unwrap_or(UNIX_EPOCH),
+    );
+    let mut outcomes = Vec::new();
+    manifest.entries.retain(|entry| {
+        if entry.quarantined_at > cutoff {
+            return true;
+        }
+        match fs::remove_dir_all(&entry.quarantined_path) {
+            // Someone already cleaned it up by hand
+            Err(e) if e.kind() == ErrorKind::NotFound => false,
+            result => {
+                let failed = result.is_err();
+                outcomes.push((entry.clone(), result));
+                failed
+            }
+        }
+    });
+    save_manifest(state, &manifest)?;
+    Ok(outcomes)
+}
+
+fn restore_entry(entry: &Entry) -> io::Result<()> {
+    // Cargo may have created a new target directory in the meantime, which must not be overwritten
+    if entry.original_path.exists() {
+        return Err(io::Error::new(
+            ErrorKind::AlreadyExists,
+            format!("{} already exists", entry.original_path.display()),
+        ));
+    }
+    fs::rename(&entry.quarantined_path, &entry.original_path)
+}
+
+/// Picks a quarantine directory on the same filesystem as `path`, so that quarantining is a rename
+fn quarantine_dir_for(state: &Path, path: &Path) -> io::Result<PathBuf> {
+    let home_quarantine = state.join("quarantine");
+    #[cfg(unix)]
+    {
+        use crate::trash::{is_on_device, mount_point};
+        use std::os::unix::fs::MetadataExt;
+        let device = fs::metadata(path)?.dev();
+        if !is_on_device(&home_quarantine, device) {
+            let uid = unsafe { libc::getuid() };
+            return Ok(mount_point(path, device).join(format!(".{APP_NAME}-quarantine-{uid}")));
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(home_quarantine)
+}
+
+fn manifest_path(state: &Path) -> PathBuf {
+    state.join("quarantine.json")
+}
+
+fn load_manifest(state: &Path) -> io::Result<Manifest> {
+    match fs::read(manifest_path(state)) {
+        Ok(contents) => Ok(serde_json::from_slice(&contents)?),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Manifest::default()),
+        Err(e) => Err(e),
+    }
+}
+
+fn save_manifest(state: &Path, manifest: &Manifest) -> io::Result<()> {
+    let path = manifest_path(state);
+    fs::create_dir_all(path.parent().unwrap())?;
+    // Write to a temporary file first so that a crash never leaves a truncated manifest behind
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_vec_pretty(manifest)?)?;
+    fs::rename(&temp_path, &path)
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{add, tree};
+
+    /// Makes target directories for the projects named in `projects`, along with an empty state
+    /// directory
+    fn targets(projects: &[&str]) -> (tempfile::TempDir, PathBuf, Vec<PathBuf>) {
+        let dir = tree(&[("state/", "")]);
+        let root = dir.path().canonicalize().unwrap();
+        let targets = projects
+            .iter()
+            .map(|project| {
+                let target = root.join(project).join("target");
+                add(&target, &[("debug/output", project)]);
+                target
+            })
+            .collect();
+        (dir, root.join("state"), targets)
+    }
+
+    fn quarantined(state: &Path) -> Vec<PathBuf> {
+        let manifest = load_manifest(state).unwrap();
+        manifest
+            .entries
+            .into_iter()
+            .map(|entry| entry.original_path)
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_the_manifest() {
+        let (_dir, state, targets) = targets(&["a", "a"]);
+        let run = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let path = quarantine_in(&state, &targets[0], 100, run).unwrap();
+        assert!(!targets[0].exists());
+        assert!(path.starts_with(state.join("quarantine")));
+        assert_eq!(fs::read_to_string(path.join("debug/output")).unwrap(), "a");
+        let manifest = load_manifest(&state).unwrap();
+        save_manifest(&state, &manifest).unwrap();
+        let entries = load_manifest(&state).unwrap().entries;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].original_path, targets[0]);
+        assert_eq!(entries[0].quarantined_path, path);
+        assert_eq!(entries[0].size, 100);
+        assert_eq!(entries[0].run, 1_700_000_000);
+        assert!(!state.join("quarantine.json.tmp").exists());
+        // A second directory of the same project quarantined in the same second gets its own name
+        add(&targets[0], &[("debug/output", "again")]);
+        let second = quarantine_in(&state, &targets[0], 100, run).unwrap();
+        assert_ne!(second, path);
+        assert_eq!(
+            quarantined(&state),
+            [targets[0].clone(), targets[0].clone()]
+        );
+    }
+
+    #[test]
+    fn undoes_the_most_recent_run() {
+        let (_dir, state, targets) = targets(&["a", "b", "c"]);
+        let first_run = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let last_run = first_run + Duration::from_secs(60);
+        quarantine_in(&state, &targets[0], 1, first_run).unwrap();
+        quarantine_in(&state, &targets[1], 1, last_run).unwrap();
+        quarantine_in(&state, &targets[2], 1, last_run).unwrap();
+        // A new target directory in the way of restoring is never overwritten
+        add(&targets[2], &[("new", "")]);
+        let outcomes = undo_in(&state).unwrap();
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes[0].1.is_ok());
+        let error = outcomes[1].1.as_ref().unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(
+            fs::read_to_string(targets[1].join("debug/output")).unwrap(),
+            "b"
+        );
+        assert!(!targets[0].exists());
+        assert_eq!(
+            quarantined(&state),
+            [targets[0].clone(), targets[2].clone()]
+        );
+        // Only the failed entry of the last run is left to undo
+        fs::remove_dir_all(&targets[2]).unwrap();
+        let outcomes = undo_in(&state).unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert!(targets[2].join("debug/output").is_file());
+        assert_eq!(quarantined(&state), [targets[0].clone()]);
+    }
+
+    #[test]
+    fn restores_by_original_or_quarantined_path() {
+        let (_dir, state, targets) = targets(&["a", "b"]);
+        let run = SystemTime::now();
+        quarantine_in(&state, &targets[0], 1, run).unwrap();
+        let path = quarantine_in(&state, &targets[1], 1, run).unwrap();
+        // The original path no longer exists, so it can't be canonicalized
+        let entry = restore_in(&state, &targets[0]).unwrap();
+        assert_eq!(entry.original_path, targets[0]);
+        assert!(targets[0].join("debug/output").is_file());
+        restore_in(&state, &path).unwrap();
+        assert!(targets[1].join("debug/output").is_file());
+        assert!(quarantined(&state).is_empty());
+        let error = restore_in(&state, &targets[0]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn purges_entries_older_than_the_given_age() {
+        let (_dir, state, targets) = targets(&["a", "b", "c"]);
+        let run = SystemTime::now();
+        let paths: Vec<_> = targets
+            .iter()
+            .map(|target| quarantine_in(&state, target, 1, run).unwrap())
+            .collect();
+        let mut manifest = load_manifest(&state).unwrap();
+        manifest.entries[0].quarantined_at -= 2 * 24 * 60 * 60;
+        manifest.entries[1].quarantined_at -= 2 * 24 * 60 * 60;
+        save_manifest(&state, &manifest).unwrap();
+        // Entries deleted by hand are forgotten without being reported
+        fs::remove_dir_all(&paths[1]).unwrap();
+        let outcomes = purge_in(&state, Duration::from_secs(24 * 60 * 60)).unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].0.original_path, targets[0]);
+        assert!(outcomes[0].1.is_ok());
+        assert!(!paths[0].exists());
+        assert!(paths[2].exists());
+        assert_eq!(quarantined(&state), [targets[2].clone()]);
+        let outcomes = purge_in(&state, Duration::ZERO).unwrap();
+        assert_eq!(outcomes.len(), 1);
+        assert!(!paths[2].exists());
+        assert!(quarantined(&state).is_empty());
+    }
+}
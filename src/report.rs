@@ -0,0 +1,256 @@
+//! Everything a scan has to say, as events along with the text a human is shown for them
+//!
+//! Nothing here is printed. Reports go to whatever [`capture`] or [`forward`] is running on the
+//! thread they are made on, and are dropped without one, so the public functions that report
+//! anything run in one of them
+use serde::Serialize;
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::{serde_path, Error};
+
+/// What was decided about a target directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Decision {
+    Clean,
+    Keep,
+    /// Kept because the project asked for that with a marker file or its manifest
+    Protected,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetEvent {
+    #[serde(with = "serde_path")]
+    pub target_path: PathBuf,
+    #[serde(with = "serde_path")]
+    pub project_dir: PathBuf,
+    pub project: String,
+    pub stray: bool,
+    /// Name of the detector that found the target directory, such as "cargo"
+    pub kind: String,
+    /// Space used on disk in bytes, `None` if the target directory wasn't fully walked
+    pub size: Option<u64>,
+    /// Sum of the lengths of all files in bytes, `None` if the target directory wasn't fully walked
+    pub apparent_size: Option<u64>,
+    /// Unix time of the last activity that was found
+    pub last_activity: Option<u64>,
+    pub decision: Decision,
+    pub reasons: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorEvent {
+    #[serde(with = "serde_path")]
+    pub path: PathBuf,
+    /// The kind of IO error, such as `NotFound` or `PermissionDenied`
+    pub kind: String,
+    pub message: String,
+    #[serde(skip)]
+    pub error_kind: ErrorKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SkipEvent {
+    #[serde(with = "serde_path")]
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    /// What was done with old target directories, such as "delete" or "dry_run"
+    pub mode: &'static str,
+    pub cleaned: usize,
+    pub kept: usize,
+    pub protected: usize,
+    pub errors: usize,
+    /// Bytes freed, or that would have been freed in a dry run, as chosen by `--apparent-size`
+    pub bytes: u64,
+    /// Space freed on disk
+    pub disk_bytes: u64,
+    /// Sum of the lengths of the files that were freed
+    pub apparent_bytes: u64,
+    /// Bytes of files in cleaned target directories that weren't freed, as they have hardlinks
+    /// elsewhere, as chosen by `--apparent-size`
+    pub linked_bytes: u64,
+    pub seconds: f32,
+    /// Totals of each root, only filled in when the roots come from a configuration file
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub roots: Vec<RootSummary>,
+    /// What other values of `--days-old` would free, only filled in for a full report
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub freed_at_age: Vec<FreedAtAge>,
+}
+
+/// Totals of a single root from a configuration file
+#[derive(Debug, Clone, Serialize)]
+pub struct RootSummary {
+    #[serde(with = "serde_path")]
+    pub path: PathBuf,
+    pub name: Option<String>,
+    pub mode: &'static str,
+    pub cleaned: usize,
+    pub kept: usize,
+    pub bytes: u64,
+}
+
+/// How much a scan with a different `--days-old` would free
+#[derive(Debug, Clone, Serialize)]
+pub struct FreedAtAge {
+    pub days: u64,
+    pub targets: usize,
+    pub bytes: u64,
+}
+
+/// Something a scan has to say
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Target(TargetEvent),
+    Error(ErrorEvent),
+    Skip(SkipEvent),
+    Summary(Summary),
+}
+
+/// Something reported while output was being captured or forwarded, to be replayed later
+#[derive(Debug, Clone)]
+pub struct Record {
+    event: Event,
+    text: Option<String>,
+}
+impl Record {
+    /// An error involving `path`, `message` is the complete human readable description
+    pub fn error(path: &Path, kind: ErrorKind, message: impl Display) -> Record {
+        let message = message.to_string();
+        let event = ErrorEvent {
+            path: path.to_path_buf(),
+            kind: format!("{kind:?}"),
+            message: message.clone(),
+            error_kind: kind,
+        };
+        Record {
+            event: Event::Error(event),
+            text: Some(message),
+        }
+    }
+    /// A directory that wasn't looked into, `text` is shown in place of the event if given
+    pub fn skip(path: &Path, reason: impl Display, text: Option<String>) -> Record {
+        let event = SkipEvent {
+            path: path.to_path_buf(),
+            reason: reason.to_string(),
+        };
+        Record {
+            event: Event::Skip(event),
+            text,
+        }
+    }
+    pub fn new(event: Event, text: Option<String>) -> Record {
+        Record { event, text }
+    }
+    pub fn event(&self) -> &Event {
+        &self.event
+    }
+    /// Returns what a human is shown for this, if anything
+    pub fn text(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+    pub fn into_parts(self) -> (Event, Option<String>) {
+        (self.event, self.text)
+    }
+    pub fn is_error(&self) -> bool {
+        matches!(self.event, Event::Error(_))
+    }
+    /// Returns the error this reports, `None` for anything else
+    pub fn into_error(self) -> Option<Error> {
+        match self.event {
+            Event::Error(event) => Some(Error::Scan {
+                path: event.path,
+                kind: event.error_kind,
+                message: event.message,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Where reports go
+enum Frame {
+    Capture(Vec<Record>),
+    Forward(Box<dyn FnMut(Record)>),
+}
+
+thread_local! {
+    /// The captures and forwards running on this thread, innermost last
+    static FRAMES: RefCell<Vec<Frame>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Reports a decision about a target directory, `text` is shown to humans if given
+pub fn target(event: TargetEvent, text: Option<String>) {
+    emit(Record::new(Event::Target(event), text));
+}
+
+/// Reports an error involving `path`, `message` is the complete human readable description
+pub fn error(path: &Path, kind: ErrorKind, message: impl Display) {
+    emit(Record::error(path, kind, message));
+}
+
+/// Reports a directory that wasn't looked into, `text` is shown to humans if given
+pub fn skip(path: &Path, reason: impl Display, text: Option<String>) {
+    emit(Record::skip(path, reason, text));
+}
+
+/// Runs `f`, holding back everything it reports
+///
+/// Work running in parallel uses this to keep the output in a deterministic order. Captures nest,
+/// so this also works for rayon jobs that get stolen while waiting on other jobs, as long as
+/// every job that reports anything captures its own output
+pub fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<Record>) {
+    FRAMES.with(|frames| frames.borrow_mut().push(Frame::Capture(Vec::new())));
+    let result = f();
+    let frame = FRAMES.with(|frames| frames.borrow_mut().pop());
+    let Some(Frame::Capture(records)) = frame else {
+        unreachable!()
+    };
+    (result, records)
+}
+
+/// Runs `f`, handing everything it reports on this thread to `sink` as it happens
+///
+/// The sink runs outside of the forward, so it can [`replay`] what it is given. Work on other
+/// threads has to [`capture`] its output and replay it on this one, as with `capture`
+pub fn forward<T>(sink: impl FnMut(Record) + 'static, f: impl FnOnce() -> T) -> T {
+    FRAMES.with(|frames| frames.borrow_mut().push(Frame::Forward(Box::new(sink))));
+    let result = f();
+    FRAMES.with(|frames| frames.borrow_mut().pop());
+    result
+}
+
+/// Reports everything that was held back by [`capture`] or handed over by [`forward`]
+pub fn replay(records: impl IntoIterator<Item = Record>) {
+    for record in records {
+        emit(record);
+    }
+}
+
+fn emit(record: Record) {
+    let mut record = Some(record);
+    let forward = FRAMES.with(|frames| {
+        let mut frames = frames.borrow_mut();
+        match frames.last_mut() {
+            Some(Frame::Capture(buffer)) => {
+                buffer.extend(record.take());
+                None
+            }
+            Some(Frame::Forward(_)) => frames.pop(),
+            None => None,
+        }
+    });
+    // Without a frame the record is dropped
+    if let (Some(record), Some(Frame::Forward(mut sink))) = (record, forward) {
+        sink(record);
+        FRAMES.with(|frames| frames.borrow_mut().push(Frame::Forward(sink)));
+    }
+}
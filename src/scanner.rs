@@ -0,0 +1,108 @@
+//! Finding and measuring target directories without printing or cleaning anything, for code that
+//! embeds the cleaner
+use rayon::prelude::*;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use crate::discovery::{self, Step};
+use crate::report::{self, Record};
+use crate::{check_target, dir_key, Candidate, Error, ScanOptions, ScanState, Verdict};
+
+/// Finds the target directories below a root, along with their size and last activity
+///
+/// Projects are found the same way as by the command line tool, while deciding what to clean is
+/// left to a [`Policy`](crate::Policy) and cleaning to an [`Action`](crate::Action)
+#[derive(Debug, Clone)]
+pub struct Scanner {
+    pub root: PathBuf,
+    /// The defaults measure every target directory, with a cutoff only the old enough ones are
+    /// yielded
+    pub options: ScanOptions,
+}
+impl Scanner {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Scanner {
+            root: root.into(),
+            options: ScanOptions::default(),
+        }
+    }
+    /// Starts scanning on other threads
+    pub fn scan(&self) -> Result<Scan, Error> {
+        let key = dir_key(&self.root).map_err(|source| Error::Root {
+            path: self.root.clone(),
+            source,
+        })?;
+        let (sender, receiver) = mpsc::channel();
+        let kept = Arc::new(AtomicUsize::new(0));
+        let (root, options, scan_kept) = (self.root.clone(), self.options.clone(), kept.clone());
+        thread::spawn(move || {
+            // Anything reported outside of the jobs below would be dropped otherwise
+            let ((), records) = report::capture(|| {
+                let mut state = ScanState::default();
+                state.visited_dirs.insert(key);
+                let steps = discovery::discover(root, &options, &mut state);
+                steps
+                    .into_par_iter()
+                    .for_each_with(sender.clone(), |sender, step| match step {
+                        Step::Records(records) => send_errors(sender, records),
+                        Step::Target(job) => {
+                            let (verdict, records) =
+                                report::capture(|| check_target(&job, &options));
+                            send_errors(sender, records);
+                            match verdict {
+                                Verdict::Clean { candidate, .. } => {
+                                    let _ = sender.send(Ok(candidate));
+                                }
+                                Verdict::Keep { .. } => {
+                                    scan_kept.fetch_add(1, Ordering::Relaxed);
+                                }
+                            }
+                        }
+                    });
+            });
+            send_errors(&sender, records);
+        });
+        Ok(Scan { receiver, kept })
+    }
+}
+
+/// The target directories of a running scan, in the order they finish being measured
+///
+/// Errors are yielded along the way, with the scan carrying on past them
+#[derive(Debug)]
+pub struct Scan {
+    receiver: Receiver<Result<Candidate, Error>>,
+    kept: Arc<AtomicUsize>,
+}
+impl Scan {
+    /// Returns how many target directories were found but not yielded so far, as the options
+    /// keep them or the project opted out
+    pub fn kept(&self) -> usize {
+        self.kept.load(Ordering::Relaxed)
+    }
+    /// Returns the next target directory if one is ready, without waiting
+    ///
+    /// The outer `None` means that nothing is ready yet, the inner one that the scan is over
+    pub fn try_next(&self) -> Option<Option<Result<Candidate, Error>>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(Some(result)),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some(None),
+        }
+    }
+}
+impl Iterator for Scan {
+    type Item = Result<Candidate, Error>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+fn send_errors(sender: &Sender<Result<Candidate, Error>>, records: Vec<Record>) {
+    for error in records.into_iter().filter_map(Record::into_error) {
+        let _ = sender.send(Err(error));
+    }
+}
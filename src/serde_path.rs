@@ -0,0 +1,38 @@
+//! Serializes paths as strings when they are valid UTF-8 and as arrays of raw bytes otherwise,
+//! so that no path is ever mangled. Use with `#[serde(with = "serde_path")]`
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Repr<'a> {
+    Str(Cow<'a, str>),
+    Bytes(Vec<u8>),
+}
+
+pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    match path.to_str() {
+        Some(s) => Repr::Str(Cow::Borrowed(s)),
+        None => Repr::Bytes(path.as_os_str().as_encoded_bytes().to_vec()),
+    }
+    .serialize(serializer)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    Ok(match Repr::deserialize(deserializer)? {
+        Repr::Str(s) => PathBuf::from(s.into_owned()),
+        Repr::Bytes(bytes) => from_bytes(bytes),
+    })
+}
+
+#[cfg(unix)]
+fn from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(OsString::from_vec(bytes))
+}
+#[cfg(not(unix))]
+fn from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
@@ -0,0 +1,189 @@
+//! Moving directories into the freedesktop.org trash, so that they show up in desktop file
+//! managers and can be restored from there
+//!
+//! See <https://specifications.freedesktop.org/trash-spec/latest/>
+use std::fs::{self, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use crate::xdg;
+
+/// A trash directory, containing `files` and `info` subdirectories
+struct TrashDir {
+    path: PathBuf,
+    /// The directory that paths in `.trashinfo` files are relative to, `None` for the home trash
+    top_dir: Option<PathBuf>,
+}
+
+/// Moves the directory at `path` into the trash, returning where it ended up
+///
+/// The home trash is used if it's on the same filesystem, otherwise the trash at the top of the
+/// filesystem holding `path`. Nothing is copied across filesystems, so if neither works `path`
+/// is left untouched and an error is returned
+pub fn move_to_trash(path: &Path) -> io::Result<PathBuf> {
+    let path = path.canonicalize()?;
+    let device = fs::symlink_metadata(&path)?.dev();
+    let mut last_error = None;
+    for trash in candidate_trash_dirs(&path, device) {
+        match trash_into(&path, &trash) {
+            Ok(trashed) => return Ok(trashed),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| {
+        io::Error::new(
+            ErrorKind::Unsupported,
+            "no trash directory is available on this filesystem",
+        )
+    }))
+}
+
+/// Lists the trash directories on the same device as the directory being trashed, in order of preference
+fn candidate_trash_dirs(path: &Path, device: u64) -> Vec<TrashDir> {
+    let mut candidates = Vec::new();
+    if let Some(home_trash) = home_trash_dir() {
+        // The home trash may not exist yet, in which case its parent decides the filesystem
+        let existing = home_trash.ancestors().find(|dir| dir.exists());
+        if existing
+            .and_then(|dir| fs::metadata(dir).ok())
+            .map(|m| m.dev())
+            == Some(device)
+        {
+            candidates.push(TrashDir {
+                path: home_trash,
+                top_dir: None,
+            });
+        }
+    }
+    let top_dir = mount_point(path, device);
+    let uid = unsafe { libc::getuid() };
+    // `$topdir/.Trash` must be set up by an administrator with the sticky bit and must not be a symlink
+    let shared = top_dir.join(".Trash");
+    if let Ok(metadata) = fs::symlink_metadata(&shared) {
+        if metadata.is_dir() && metadata.permissions().mode() & 0o1000 != 0 {
+            candidates.push(TrashDir {
+                path: shared.join(uid.to_string()),
+                top_dir: Some(top_dir.clone()),
+            });
+        }
+    }
+    candidates.push(TrashDir {
+        path: top_dir.join(format!(".Trash-{uid}")),
+        top_dir: Some(top_dir),
+    });
+    candidates
+}
+
+fn home_trash_dir() -> Option<PathBuf> {
+    Some(xdg::data_home()?.join("Trash"))
+}
+
+/// Returns whether `dir` is on `device`, looking at its closest existing ancestor if it doesn't exist yet
+pub fn is_on_device(dir: &Path, device: u64) -> bool {
+    let existing = dir.ancestors().find(|dir| dir.exists());
+    existing
+        .and_then(|dir| fs::metadata(dir).ok())
+        .map(|m| m.dev())
+        == Some(device)
+}
+
+/// Returns the topmost ancestor of `path` that is still on `device`
+pub fn mount_point(path: &Path, device: u64) -> PathBuf {
+    let mut top = path;
+    for ancestor in path.ancestors().skip(1) {
+        match fs::metadata(ancestor) {
+            Ok(metadata) if metadata.dev() == device => top = ancestor,
+            _ => break,
+        }
+    }
+    top.to_path_buf()
+}
+
+fn trash_into(path: &Path, trash: &TrashDir) -> io::Result<PathBuf> {
+    let files_dir = trash.path.join("files");
+    let info_dir = trash.path.join("info");
+    fs::create_dir_all(&files_dir)?;
+    fs::create_dir_all(&info_dir)?;
+    let original_path = match &trash.top_dir {
+        Some(top_dir) => path.strip_prefix(top_dir).unwrap_or(path),
+        None => path,
+    };
+    let contents = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        percent_encode(original_path),
+        local_timestamp()
+    );
+    let base_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "target".to_owned());
+    let mut i = 0;
+    loop {
+        i += 1;
+        let name = if i == 1 {
+            base_name.clone()
+        } else {
+            format!("{base_name}.{i}")
+        };
+        let info_path = info_dir.join(format!("{name}.trashinfo"));
+        let trashed_path = files_dir.join(&name);
+        // Creating the info file exclusively is what reserves the name
+        let mut info_file = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_path)
+        {
+            Ok(v) => v,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        };
+        if trashed_path.exists() {
+            drop(info_file);
+            fs::remove_file(&info_path)?;
+            continue;
+        }
+        let result = info_file
+            .write_all(contents.as_bytes())
+            .and_then(|_| fs::rename(path, &trashed_path));
+        return match result {
+            Ok(()) => Ok(trashed_path),
+            Err(e) => {
+                let _ = fs::remove_file(&info_path);
+                Err(e)
+            }
+        };
+    }
+}
+
+/// Encodes a path the way `.trashinfo` files expect, as in URLs but keeping `/`
+fn percent_encode(path: &Path) -> String {
+    let mut encoded = String::new();
+    for &byte in path.as_os_str().as_bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+/// Formats the current local time as `YYYY-MM-DDThh:mm:ss`
+fn local_timestamp() -> String {
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        libc::localtime_r(&now, &mut tm);
+    }
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
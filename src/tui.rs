@@ -0,0 +1,837 @@
+//! A terminal UI listing target directories as the scan finds them, from which a selection of them
+//! can be deleted or trashed
+//!
+//! Everything shown is held in [`App`], which only changes through messages from the scan and
+//! [`App::handle_key`], and [`draw`] renders it to any ratatui backend. [`run_app`] takes its
+//! events from a function, so the whole UI can be driven by made up key presses against a
+//! `TestBackend` without a terminal
+use humansize::DECIMAL;
+use ratatui::backend::Backend;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Flex, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{Frame, Terminal};
+use std::collections::BTreeSet;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use code_workspaces_cleaner_upper::action::{self, Delete, Trash};
+use code_workspaces_cleaner_upper::{unix_secs, Candidate, CleanMode, Scan, Scanner};
+
+/// A target directory that can be cleaned
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub candidate: Candidate,
+    /// Size shown and sorted by, which is the apparent size with `--apparent-size`
+    pub size: u64,
+}
+
+/// What the scan running in the background tells the UI
+#[derive(Debug)]
+pub enum Message {
+    Found(Entry),
+    /// How many target directories that can't be cleaned were found so far, such as protected ones
+    Kept(usize),
+    Error(String),
+    /// The scan is over, with how many target directories it kept in the end
+    Done(usize),
+}
+
+/// The column the table is sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Size,
+    Age,
+    Path,
+    Ecosystem,
+}
+impl SortKey {
+    fn next(self) -> SortKey {
+        match self {
+            SortKey::Size => SortKey::Age,
+            SortKey::Age => SortKey::Path,
+            SortKey::Path => SortKey::Ecosystem,
+            SortKey::Ecosystem => SortKey::Size,
+        }
+    }
+}
+
+/// What typed keys go to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Normal,
+    /// Editing the filter on paths and project names
+    Name,
+    /// Editing the minimum age in days, which is only applied once it is entered
+    Age(String),
+    /// Waiting for cleaning the selection this way to be confirmed
+    Confirm(CleanMode),
+}
+
+/// Something the caller of [`App::handle_key`] has to do
+#[derive(Debug)]
+pub enum Action {
+    Quit,
+    Clean(CleanMode, Vec<Candidate>),
+}
+
+/// Everything the UI shows
+#[derive(Debug)]
+pub struct App {
+    entries: Vec<Entry>,
+    sort: SortKey,
+    descending: bool,
+    /// Target paths of the selected entries
+    selected: BTreeSet<PathBuf>,
+    /// Position of the highlighted row among the shown ones
+    cursor: usize,
+    name_filter: String,
+    /// Only entries without activity in this many days are shown
+    min_age: Option<u64>,
+    input: Input,
+    scanning: bool,
+    kept: usize,
+    errors: usize,
+    /// Shown instead of the key help until the next key is pressed
+    status: Option<String>,
+    /// Unix time ages are measured from
+    now: u64,
+}
+impl App {
+    /// Creates an empty UI that measures ages from the unix time `now`
+    pub fn new(now: u64, min_age: Option<u64>) -> Self {
+        App {
+            entries: Vec::new(),
+            sort: SortKey::Size,
+            descending: true,
+            selected: BTreeSet::new(),
+            cursor: 0,
+            name_filter: String::new(),
+            min_age,
+            input: Input::Normal,
+            scanning: true,
+            kept: 0,
+            errors: 0,
+            status: None,
+            now,
+        }
+    }
+    pub fn receive(&mut self, message: Message) {
+        match message {
+            Message::Found(entry) => self.entries.push(entry),
+            Message::Kept(kept) => self.kept = kept,
+            Message::Error(message) => {
+                self.errors += 1;
+                self.status = Some(message);
+            }
+            Message::Done(kept) => {
+                self.kept = kept;
+                self.scanning = false;
+            }
+        }
+    }
+    /// Returns the age of `entry` in days, `None` if it has no known activity
+    fn age(&self, entry: &Entry) -> Option<u64> {
+        entry
+            .candidate
+            .last_activity
+            .map(|time| self.now.saturating_sub(time) / (3600 * 24))
+    }
+    /// Returns the entries that pass the filters, in the order they are shown
+    pub fn shown(&self) -> Vec<&Entry> {
+        let name_filter = self.name_filter.to_lowercase();
+        let mut shown: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|entry| {
+                name_filter.is_empty()
+                    || entry
+                        .candidate
+                        .target_path
+                        .to_string_lossy()
+                        .to_lowercase()
+                        .contains(&name_filter)
+                    || entry
+                        .candidate
+                        .project
+                        .to_lowercase()
+                        .contains(&name_filter)
+            })
+            // Without any known activity a target directory is as old as it gets
+            .filter(|entry| {
+                self.min_age
+                    .is_none_or(|days| self.age(entry).is_none_or(|age| age >= days))
+            })
+            .collect();
+        shown.sort_by(|a, b| {
+            let order = match self.sort {
+                SortKey::Size => a.size.cmp(&b.size),
+                SortKey::Age => self
+                    .age(a)
+                    .unwrap_or(u64::MAX)
+                    .cmp(&self.age(b).unwrap_or(u64::MAX)),
+                SortKey::Path => a.candidate.target_path.cmp(&b.candidate.target_path),
+                SortKey::Ecosystem => a.candidate.kind.cmp(&b.candidate.kind),
+            };
+            // The path breaks ties so that rows don't jump around while the scan fills the table
+            let order = order.then_with(|| a.candidate.target_path.cmp(&b.candidate.target_path));
+            if self.descending {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+        shown
+    }
+    /// Returns the shown entries that are selected, which are the ones that would be cleaned
+    fn selection(&self) -> Vec<&Entry> {
+        self.shown()
+            .into_iter()
+            .filter(|entry| self.selected.contains(&entry.candidate.target_path))
+            .collect()
+    }
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if key.kind == KeyEventKind::Release {
+            return None;
+        }
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return Some(Action::Quit);
+        }
+        self.status = None;
+        match &mut self.input {
+            Input::Normal => return self.handle_normal_key(key),
+            Input::Name => match key.code {
+                KeyCode::Char(c) => self.name_filter.push(c),
+                KeyCode::Backspace => {
+                    self.name_filter.pop();
+                }
+                KeyCode::Enter => self.input = Input::Normal,
+                KeyCode::Esc => {
+                    self.name_filter.clear();
+                    self.input = Input::Normal;
+                }
+                _ => (),
+            },
+            Input::Age(text) => match key.code {
+                KeyCode::Char(c) if c.is_ascii_digit() => text.push(c),
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Enter => {
+                    self.min_age = text.parse().ok().filter(|&days| days > 0);
+                    self.input = Input::Normal;
+                }
+                KeyCode::Esc => self.input = Input::Normal,
+                _ => (),
+            },
+            Input::Confirm(mode) => {
+                let mode = *mode;
+                self.input = Input::Normal;
+                if key.code != KeyCode::Char('y') {
+                    self.status = Some("Nothing was cleaned".to_owned());
+                    return None;
+                }
+                let candidates = self
+                    .selection()
+                    .into_iter()
+                    .map(|entry| entry.candidate.clone())
+                    .collect();
+                return Some(Action::Clean(mode, candidates));
+            }
+        }
+        // The filters changed, so the highlighted row may be gone
+        self.cursor = 0;
+        None
+    }
+    fn handle_normal_key(&mut self, key: KeyEvent) -> Option<Action> {
+        let shown = self.shown().len();
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Some(Action::Quit),
+            KeyCode::Up | KeyCode::Char('k') => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.cursor += 1,
+            KeyCode::PageUp => self.cursor = self.cursor.saturating_sub(10),
+            KeyCode::PageDown => self.cursor += 10,
+            KeyCode::Home | KeyCode::Char('g') => self.cursor = 0,
+            KeyCode::End | KeyCode::Char('G') => self.cursor = shown.saturating_sub(1),
+            KeyCode::Char(' ') => {
+                if let Some(entry) = self.shown().get(self.cursor) {
+                    let path = entry.candidate.target_path.clone();
+                    if !self.selected.remove(&path) {
+                        self.selected.insert(path);
+                    }
+                    self.cursor += 1;
+                }
+            }
+            KeyCode::Char('a') => {
+                let paths: Vec<_> = self
+                    .shown()
+                    .iter()
+                    .map(|entry| entry.candidate.target_path.clone())
+                    .collect();
+                if paths.iter().all(|path| self.selected.contains(path)) {
+                    for path in &paths {
+                        self.selected.remove(path);
+                    }
+                } else {
+                    self.selected.extend(paths);
+                }
+            }
+            KeyCode::Char('s') => self.sort = self.sort.next(),
+            KeyCode::Char('r') => self.descending = !self.descending,
+            KeyCode::Char('/') => self.input = Input::Name,
+            KeyCode::Char('o') => self.input = Input::Age(String::new()),
+            KeyCode::Char('d') | KeyCode::Char('t') => {
+                if self.selection().is_empty() {
+                    self.status = Some("Select target directories with space first".to_owned());
+                } else if key.code == KeyCode::Char('d') {
+                    self.input = Input::Confirm(CleanMode::Delete);
+                } else {
+                    self.input = Input::Confirm(CleanMode::Trash);
+                }
+            }
+            _ => (),
+        }
+        self.cursor = self.cursor.min(shown.saturating_sub(1));
+        None
+    }
+    /// Takes the target directories that were cleaned out of the table
+    ///
+    /// `results` holds each target directory that was to be cleaned, its size and whether
+    /// cleaning it worked, and `errors` what went wrong
+    pub fn cleaned(
+        &mut self,
+        mode: CleanMode,
+        results: &[(PathBuf, u64, bool)],
+        errors: &[String],
+    ) {
+        let (mut count, mut bytes) = (0, 0);
+        for (path, size, success) in results {
+            if *success {
+                self.entries
+                    .retain(|entry| &entry.candidate.target_path != path);
+                self.selected.remove(path);
+                count += 1;
+                bytes += size;
+            }
+        }
+        self.errors += errors.len();
+        let mut status = format!(
+            "{} {} of data in {} target folders",
+            mode.past_tense(),
+            humansize::format_size(bytes, DECIMAL),
+            count
+        );
+        if let Some(error) = errors.last() {
+            status += &format!(", {} failed: {}", results.len() - count, error);
+        }
+        self.status = Some(status);
+        self.cursor = self.cursor.min(self.shown().len().saturating_sub(1));
+    }
+    /// Describes the scan and the filters in the title line
+    fn title(&self) -> String {
+        let shown = self.shown();
+        let mut title = format!(
+            "{} {} target folders with {}",
+            if self.scanning {
+                "Scanning, found"
+            } else {
+                "Found"
+            },
+            self.entries.len(),
+            humansize::format_size(
+                self.entries.iter().map(|entry| entry.size).sum::<u64>(),
+                DECIMAL
+            )
+        );
+        if shown.len() != self.entries.len() {
+            title += &format!(", {} shown", shown.len());
+        }
+        let selection = self.selection();
+        if !selection.is_empty() {
+            title += &format!(
+                ", {} selected with {}",
+                selection.len(),
+                humansize::format_size(
+                    selection.iter().map(|entry| entry.size).sum::<u64>(),
+                    DECIMAL
+                )
+            );
+        }
+        if self.kept > 0 {
+            title += &format!(", {} protected", self.kept);
+        }
+        if self.errors > 0 {
+            title += &format!(", {} errors", self.errors);
+        }
+        if !self.name_filter.is_empty() {
+            title += &format!(" | matching {:?}", self.name_filter);
+        }
+        if let Some(days) = self.min_age {
+            title += &format!(" | older than {days} days");
+        }
+        title
+    }
+    /// Describes the keys that can be pressed, or the text being entered
+    fn footer(&self) -> String {
+        if let Some(status) = &self.status {
+            return status.clone();
+        }
+        match &self.input {
+            Input::Normal => "↑↓ move  space select  a select all  s sort  r reverse  / filter name  o filter age  d delete  t trash  q quit".to_owned(),
+            Input::Name => format!("Filter by name: {}█  enter done  esc clear", self.name_filter),
+            Input::Age(text) => format!("Only show target folders older than: {text}█ days  enter done  esc cancel"),
+            Input::Confirm(_) => "y confirm  any other key cancels".to_owned(),
+        }
+    }
+}
+
+/// Renders `app` to the whole frame
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [title_area, table_area, footer_area] = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Min(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    frame.render_widget(
+        Paragraph::new(app.title()).style(Style::new().add_modifier(Modifier::BOLD)),
+        title_area,
+    );
+    let header = [
+        (None, ""),
+        (Some(SortKey::Size), "Size"),
+        (Some(SortKey::Age), "Age"),
+        (Some(SortKey::Ecosystem), "Ecosystem"),
+        (Some(SortKey::Path), "Path"),
+    ]
+    .map(|(key, name)| match key {
+        Some(key) if key == app.sort => {
+            format!("{name} {}", if app.descending { "▼" } else { "▲" })
+        }
+        _ => name.to_owned(),
+    });
+    let rows = app.shown().into_iter().map(|entry| {
+        let selected = app.selected.contains(&entry.candidate.target_path);
+        let row = Row::new([
+            if selected { "[x]" } else { "[ ]" }.to_owned(),
+            humansize::format_size(entry.size, DECIMAL),
+            match app.age(entry) {
+                Some(1) => "1 day".to_owned(),
+                Some(days) => format!("{days} days"),
+                None => "unknown".to_owned(),
+            },
+            entry.candidate.kind.clone(),
+            format!(
+                "{}{} ({})",
+                entry.candidate.target_path.display(),
+                if entry.candidate.stray { ", stray" } else { "" },
+                entry.candidate.project
+            ),
+        ]);
+        if selected {
+            row.style(Style::new().add_modifier(Modifier::BOLD))
+        } else {
+            row
+        }
+    });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Length(3),
+            Constraint::Length(10),
+            Constraint::Length(10),
+            // Room for "Ecosystem ▼"
+            Constraint::Length(11),
+            Constraint::Fill(1),
+        ],
+    )
+    .header(Row::new(header).style(Style::new().add_modifier(Modifier::UNDERLINED)))
+    .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+    let mut state = TableState::default().with_selected(Some(app.cursor));
+    frame.render_stateful_widget(table, table_area, &mut state);
+    frame.render_widget(Paragraph::new(app.footer()), footer_area);
+    if let Input::Confirm(mode) = app.input {
+        let selection = app.selection();
+        let question = format!(
+            "{} {} target folders with {}?",
+            match mode {
+                CleanMode::Trash => "Move to the trash",
+                _ => "Permanently delete",
+            },
+            selection.len(),
+            humansize::format_size(
+                selection.iter().map(|entry| entry.size).sum::<u64>(),
+                DECIMAL
+            )
+        );
+        let [area] = Layout::horizontal([Constraint::Length(50)])
+            .flex(Flex::Center)
+            .areas(frame.area());
+        let [area] = Layout::vertical([Constraint::Length(5)])
+            .flex(Flex::Center)
+            .areas(area);
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(format!("{question}\n[y]es / [n]o"))
+                .wrap(Wrap { trim: true })
+                .block(Block::new().borders(Borders::ALL).title("Confirm")),
+            area,
+        );
+    }
+}
+
+/// Scans in the background and runs the UI on the terminal until it is quit
+///
+/// Only target directories older than `min_age` days are shown at first
+pub fn run(scanner: Scanner, min_age: Option<u64>) -> io::Result<()> {
+    let scan = scanner.scan().map_err(io::Error::other)?;
+    let mut app = App::new(unix_secs(scanner.options.started), min_age);
+    let mut terminal = ratatui::try_init()?;
+    let result = run_app(
+        &mut terminal,
+        &mut app,
+        scan,
+        scanner.options.apparent_size,
+        |timeout| {
+            if event::poll(timeout)? {
+                event::read().map(Some)
+            } else {
+                Ok(None)
+            }
+        },
+    );
+    ratatui::restore();
+    result
+}
+
+/// Runs the UI until it is quit, with `next_event` waiting up to the given time for an event
+pub fn run_app<B: Backend>(
+    terminal: &mut Terminal<B>,
+    app: &mut App,
+    scan: Scan,
+    apparent_size: bool,
+    mut next_event: impl FnMut(Duration) -> io::Result<Option<Event>>,
+) -> io::Result<()> {
+    let mut scan = Some(scan);
+    loop {
+        while let Some(next) = scan.as_ref().and_then(Scan::try_next) {
+            let message = match next {
+                Some(Ok(candidate)) => Message::Found(Entry {
+                    size: if apparent_size {
+                        candidate.apparent_size
+                    } else {
+                        candidate.size
+                    },
+                    candidate,
+                }),
+                Some(Err(e)) => Message::Error(e.to_string()),
+                // Directories may have been kept since the count was last sent
+                None => Message::Done(scan.take().map_or(0, |scan| scan.kept())),
+            };
+            app.receive(message);
+        }
+        if let Some(scan) = &scan {
+            app.receive(Message::Kept(scan.kept()));
+        }
+        terminal.draw(|frame| draw(frame, app))?;
+        let Some(Event::Key(key)) = next_event(Duration::from_millis(100))? else {
+            continue;
+        };
+        match app.handle_key(key) {
+            Some(Action::Quit) => return Ok(()),
+            Some(Action::Clean(mode, candidates)) => {
+                app.status = Some(format!("Cleaning {} target folders...", candidates.len()));
+                terminal.draw(|frame| draw(frame, app))?;
+                let (results, errors) = clean(&candidates, mode, apparent_size);
+                app.cleaned(mode, &results, &errors);
+            }
+            None => (),
+        }
+    }
+}
+
+/// Deletes or trashes `candidates`, returning the path, size and success of each along with the
+/// errors
+fn clean(
+    candidates: &[Candidate],
+    mode: CleanMode,
+    apparent_size: bool,
+) -> (Vec<(PathBuf, u64, bool)>, Vec<String>) {
+    let mut action: Box<dyn action::Action> = match mode {
+        CleanMode::Trash => Box::new(Trash),
+        _ => Box::new(Delete),
+    };
+    let mut errors = Vec::new();
+    let results = candidates
+        .iter()
+        .map(|candidate| {
+            let size = if apparent_size {
+                candidate.apparent_size
+            } else {
+                candidate.size
+            };
+            let result = action.apply(candidate);
+            if let Err(e) = &result {
+                errors.push(e.to_string());
+            }
+            (candidate.target_path.clone(), size, result.is_ok())
+        })
+        .collect();
+    (results, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use std::fs;
+
+    const DAY: u64 = 3600 * 24;
+    const NOW: u64 = 1000 * DAY;
+
+    fn entry(path: &str, name: &str, kind: &str, size: u64, age: Option<u64>) -> Entry {
+        let target_path = PathBuf::from(path);
+        Entry {
+            candidate: Candidate {
+                project_dir: target_path.parent().unwrap().to_path_buf(),
+                target_path,
+                project: format!("package {name}"),
+                stray: false,
+                size,
+                apparent_size: size,
+                newest_modified: None,
+                newest_modified_nanos: 0,
+                last_activity: age.map(|days| NOW - days * DAY),
+                kind: kind.to_owned(),
+                reasons: Vec::new(),
+                hardlinks: Vec::new(),
+            },
+            size,
+        }
+    }
+
+    fn app() -> App {
+        let mut app = App::new(NOW, None);
+        app.receive(Message::Found(entry(
+            "/w/alpha/target",
+            "alpha",
+            "cargo",
+            300,
+            Some(10),
+        )));
+        app.receive(Message::Found(entry(
+            "/w/beta/.venv",
+            "beta",
+            "python",
+            100,
+            Some(40),
+        )));
+        app.receive(Message::Found(entry(
+            "/w/gamma/node_modules",
+            "gamma",
+            "node",
+            200,
+            None,
+        )));
+        app
+    }
+
+    fn press(app: &mut App, keys: &str) -> Option<Action> {
+        let mut action = None;
+        for c in keys.chars() {
+            action = app.handle_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+        action
+    }
+
+    fn key(app: &mut App, code: KeyCode) -> Option<Action> {
+        app.handle_key(KeyEvent::from(code))
+    }
+
+    /// Returns the names of the shown projects, in order
+    fn shown(app: &App) -> Vec<&str> {
+        app.shown()
+            .into_iter()
+            .map(|entry| entry.candidate.project.trim_start_matches("package "))
+            .collect()
+    }
+
+    fn screen(app: &App) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(120, 12)).unwrap();
+        terminal.draw(|frame| draw(frame, app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        buffer.content().iter().map(|cell| cell.symbol()).collect()
+    }
+
+    #[test]
+    fn sorts_by_each_column() {
+        let mut app = app();
+        assert_eq!(shown(&app), ["alpha", "gamma", "beta"]);
+        press(&mut app, "r");
+        assert_eq!(shown(&app), ["beta", "gamma", "alpha"]);
+        press(&mut app, "rs");
+        assert_eq!(app.sort, SortKey::Age);
+        // Without any known activity a target directory counts as the oldest
+        assert_eq!(shown(&app), ["gamma", "beta", "alpha"]);
+        press(&mut app, "sr");
+        assert_eq!(app.sort, SortKey::Path);
+        assert_eq!(shown(&app), ["alpha", "beta", "gamma"]);
+        press(&mut app, "rs");
+        assert_eq!(app.sort, SortKey::Ecosystem);
+        assert_eq!(shown(&app), ["beta", "gamma", "alpha"]);
+        assert!(screen(&app).contains("Ecosystem ▼"));
+        press(&mut app, "s");
+        assert_eq!(app.sort, SortKey::Size);
+        assert_eq!(shown(&app), ["alpha", "gamma", "beta"]);
+    }
+
+    #[test]
+    fn filters_by_name() {
+        let mut app = app();
+        press(&mut app, "/ALP");
+        assert_eq!(app.input, Input::Name);
+        assert_eq!(shown(&app), ["alpha"]);
+        key(&mut app, KeyCode::Enter);
+        assert_eq!(app.input, Input::Normal);
+        assert_eq!(shown(&app), ["alpha"]);
+        assert!(app.title().contains("1 shown | matching \"ALP\""));
+        // Project names match too, not just paths
+        press(&mut app, "/");
+        for _ in 0..3 {
+            key(&mut app, KeyCode::Backspace);
+        }
+        press(&mut app, "package b");
+        assert_eq!(shown(&app), ["beta"]);
+        key(&mut app, KeyCode::Esc);
+        assert_eq!(app.input, Input::Normal);
+        assert_eq!(shown(&app), ["alpha", "gamma", "beta"]);
+    }
+
+    #[test]
+    fn filters_by_age() {
+        let mut app = app();
+        // Only digits are taken, and nothing changes until the age is entered
+        press(&mut app, "o3x0");
+        assert_eq!(app.input, Input::Age("30".to_owned()));
+        assert_eq!(shown(&app).len(), 3);
+        key(&mut app, KeyCode::Enter);
+        assert_eq!(shown(&app), ["gamma", "beta"]);
+        assert!(app.title().ends_with(" | older than 30 days"));
+        press(&mut app, "o5");
+        key(&mut app, KeyCode::Esc);
+        assert_eq!(app.min_age, Some(30));
+        press(&mut app, "o");
+        key(&mut app, KeyCode::Enter);
+        assert_eq!(app.min_age, None);
+        assert_eq!(shown(&app).len(), 3);
+    }
+
+    #[test]
+    fn selects_shown_entries() {
+        let mut app = app();
+        press(&mut app, " ");
+        assert_eq!(app.cursor, 1);
+        assert_eq!(
+            app.selected,
+            BTreeSet::from([PathBuf::from("/w/alpha/target")])
+        );
+        key(&mut app, KeyCode::Up);
+        press(&mut app, " ");
+        assert!(app.selected.is_empty());
+        press(&mut app, "a");
+        assert_eq!(app.selected.len(), 3);
+        assert!(app.title().contains("3 selected with 600 B"));
+        press(&mut app, "a");
+        assert!(app.selected.is_empty());
+        // Hidden entries stay selected, but aren't cleaned
+        press(&mut app, "G ");
+        assert_eq!(app.selection().len(), 1);
+        press(&mut app, "/alpha");
+        key(&mut app, KeyCode::Enter);
+        assert!(app.selection().is_empty());
+        assert!(press(&mut app, "d").is_none());
+        assert_eq!(app.input, Input::Normal);
+        assert_eq!(app.footer(), "Select target directories with space first");
+    }
+
+    #[test]
+    fn confirms_and_cancels_cleaning() {
+        let mut app = app();
+        press(&mut app, " d");
+        assert_eq!(app.input, Input::Confirm(CleanMode::Delete));
+        assert!(screen(&app).contains("Permanently delete 1 target folders with 300 B?"));
+        assert!(press(&mut app, "n").is_none());
+        assert_eq!(app.input, Input::Normal);
+        assert_eq!(app.footer(), "Nothing was cleaned");
+        press(&mut app, "t");
+        assert!(screen(&app).contains("Move to the trash 1 target folders with 300 B?"));
+        match press(&mut app, "y") {
+            Some(Action::Clean(CleanMode::Trash, candidates)) => {
+                assert_eq!(candidates.len(), 1);
+                assert_eq!(candidates[0].target_path, PathBuf::from("/w/alpha/target"));
+            }
+            action => panic!("expected the selection to be trashed, got {action:?}"),
+        }
+        assert!(matches!(press(&mut app, "q"), Some(Action::Quit)));
+        press(&mut app, "/");
+        let ctrl_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+        assert!(matches!(app.handle_key(ctrl_c), Some(Action::Quit)));
+    }
+
+    #[test]
+    fn receives_scan_messages() {
+        let mut app = app();
+        assert!(app
+            .title()
+            .starts_with("Scanning, found 3 target folders with 600 B"));
+        app.receive(Message::Kept(1));
+        app.receive(Message::Error("Error reading something".to_owned()));
+        assert!(app.title().ends_with(", 1 protected, 1 errors"));
+        app.receive(Message::Done(2));
+        assert_eq!(
+            app.title(),
+            "Found 3 target folders with 600 B, 2 protected, 1 errors"
+        );
+        let screen = screen(&app);
+        assert!(screen.contains("Error reading something"));
+        assert!(screen.contains("[ ] 200 B"));
+        // The next key press clears the status
+        key(&mut app, KeyCode::Down);
+        assert!(app.footer().starts_with("↑↓ move"));
+    }
+
+    #[test]
+    fn deletes_selection_from_run_app() {
+        let dir = std::env::temp_dir().join(format!("tui-test-{}", std::process::id()));
+        let root = dir.join("empty");
+        let target_path = dir.join("project").join("target");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(target_path.join("debug")).unwrap();
+        fs::write(target_path.join("debug").join("output"), "output").unwrap();
+        let mut app = App::new(NOW, None);
+        app.receive(Message::Found(entry(
+            target_path.to_str().unwrap(),
+            "project",
+            "cargo",
+            1000,
+            Some(100),
+        )));
+        let scan = Scanner::new(&root).scan().unwrap();
+        let mut terminal = Terminal::new(TestBackend::new(120, 12)).unwrap();
+        let mut keys = [' ', 'd', 'y', 'q'].into_iter();
+        let result = run_app(&mut terminal, &mut app, scan, false, |_| {
+            Ok(keys.next().map(|c| Event::Key(KeyCode::Char(c).into())))
+        });
+        let deleted = !target_path.exists();
+        fs::remove_dir_all(&dir).unwrap();
+        result.unwrap();
+        assert!(deleted);
+        assert!(app.shown().is_empty());
+        assert!(app.selected.is_empty());
+        let buffer = terminal.backend().buffer();
+        let screen: String = buffer.content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("Deleted 1 kB of data in 1 target folders"));
+    }
+}
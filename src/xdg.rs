@@ -0,0 +1,37 @@
+//! Base directories from the XDG base directory specification
+use std::env;
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+
+/// Name of the directory this tool keeps its own files in
+pub const APP_NAME: &str = "code-workspaces-cleaner-upper";
+
+/// Returns `$XDG_DATA_HOME`, defaulting to `~/.local/share`
+pub fn data_home() -> Option<PathBuf> {
+    base_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// Returns `$XDG_CONFIG_HOME`, defaulting to `~/.config`
+pub fn config_home() -> Option<PathBuf> {
+    base_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// Returns `$XDG_STATE_HOME`, defaulting to `~/.local/state`
+pub fn state_home() -> Option<PathBuf> {
+    base_dir("XDG_STATE_HOME", ".local/state")
+}
+
+/// Returns the directory this tool keeps its state in, such as the quarantine manifest
+pub fn state_dir() -> io::Result<PathBuf> {
+    state_home()
+        .map(|dir| dir.join(APP_NAME))
+        .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "couldn't find the home directory"))
+}
+
+fn base_dir(var: &str, default: &str) -> Option<PathBuf> {
+    match env::var_os(var) {
+        // The spec says relative paths are invalid and should be ignored
+        Some(dir) if PathBuf::from(&dir).is_absolute() => Some(PathBuf::from(dir)),
+        _ => Some(PathBuf::from(env::var_os("HOME")?).join(default)),
+    }
+}